@@ -0,0 +1,405 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use std::collections::HashSet;
+use syn::{
+    braced,
+    meta::ParseNestedMeta,
+    parenthesized,
+    parse::{Parse, ParseStream},
+    spanned::Spanned,
+    token::{Brace, Paren},
+    Attribute, Expr, Ident, LitStr, Path, Token,
+};
+
+pub(crate) const ENUM_COMPONENT: &str = "enum_component";
+
+pub(crate) const STORAGE_TYPE: &str = "storage_type";
+pub(crate) const TABLE: &str = "table";
+pub(crate) const SPARSE_SET: &str = "sparse_set";
+pub(crate) const MUTABILITY: &str = "mutability";
+pub(crate) const MUTABLE: &str = "mutable";
+pub(crate) const IMMUTABLE: &str = "immutable";
+pub(crate) const CRATE: &str = "crate";
+pub(crate) const ON_ADD: &str = "on_add";
+pub(crate) const ON_INSERT: &str = "on_insert";
+pub(crate) const ON_REPLACE: &str = "on_replace";
+pub(crate) const ON_REMOVE: &str = "on_remove";
+pub(crate) const ON_DESPAWN: &str = "on_despawn";
+pub(crate) const REQUIRE: &str = "require";
+pub(crate) const CLONE_BEHAVIOR: &str = "clone_behavior";
+pub(crate) const TRANSITIONS: &str = "transitions";
+pub(crate) const STRICT_TRANSITIONS: &str = "strict_transitions";
+pub(crate) const BUNDLE: &str = "bundle";
+pub(crate) const ENTITIES: &str = "entities";
+
+const ENUM_ATTRS: &[&str] = &[
+    STORAGE_TYPE,
+    TABLE,
+    SPARSE_SET,
+    MUTABILITY,
+    MUTABLE,
+    IMMUTABLE,
+    CRATE,
+    ON_ADD,
+    ON_INSERT,
+    ON_REPLACE,
+    ON_REMOVE,
+    ON_DESPAWN,
+    REQUIRE,
+    CLONE_BEHAVIOR,
+    TRANSITIONS,
+    STRICT_TRANSITIONS,
+];
+
+const VARIANT_ATTRS: &[&str] = &[REQUIRE, BUNDLE];
+
+const FIELD_ATTRS: &[&str] = &[ENTITIES];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum StorageTy {
+    Table,
+    SparseSet,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MutabilityTy {
+    Mutable,
+    Immutable,
+}
+
+/// A validated attribute value, along with the path the user wrote for it, if any.
+pub(crate) type Spelled<T> = (T, Option<Path>);
+
+/// Everything given through `#[enum_component(...)]` on the enum itself.
+#[derive(Default)]
+pub(crate) struct EnumAttrs {
+    pub(crate) storage_type: Option<Spelled<StorageTy>>,
+    pub(crate) mutability: Option<Spelled<MutabilityTy>>,
+    pub(crate) crate_path: Option<Path>,
+    pub(crate) hooks: Hooks,
+    pub(crate) requires: Vec<Require>,
+    /// Written relative to `ComponentCloneBehavior`, as in bevy's `#[component(clone_behavior = Ignore)]`.
+    pub(crate) clone_behavior: Option<Expr>,
+    pub(crate) transitions: Option<Transitions>,
+}
+
+/// User hooks, called after the generated hook of the same name. The bookkeeping of the generated hook is up to
+/// date by then, but the markers are only inserted or removed by the command it queued, which runs before the
+/// commands queued by the user hook.
+#[derive(Default)]
+pub(crate) struct Hooks {
+    pub(crate) on_add: Option<Expr>,
+    pub(crate) on_insert: Option<Expr>,
+    pub(crate) on_replace: Option<Expr>,
+    pub(crate) on_remove: Option<Expr>,
+    pub(crate) on_despawn: Option<Expr>,
+}
+
+impl EnumAttrs {
+    /// Parses every `#[enum_component(...)]` attribute, each of which may hold a comma-separated list.
+    pub(crate) fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut result = Self::default();
+        let mut required = HashSet::new();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident(ENUM_COMPONENT)) {
+            attr.parse_nested_meta(|meta| {
+                let path = &meta.path;
+
+                if path.is_ident(STORAGE_TYPE) {
+                    let value = meta.value()?.parse::<Path>()?;
+                    let storage_type = (parse_storage_type(&value)?, Some(value));
+                    set_once(&mut result.storage_type, storage_type, path, "storage type")
+                } else if path.is_ident(TABLE) {
+                    set_once(&mut result.storage_type, (StorageTy::Table, None), path, "storage type")
+                } else if path.is_ident(SPARSE_SET) {
+                    set_once(&mut result.storage_type, (StorageTy::SparseSet, None), path, "storage type")
+                } else if path.is_ident(MUTABILITY) {
+                    let value = meta.value()?.parse::<Path>()?;
+                    let mutability = (parse_mutability(&value)?, Some(value));
+                    set_once(&mut result.mutability, mutability, path, "mutability")
+                } else if path.is_ident(MUTABLE) {
+                    set_once(&mut result.mutability, (MutabilityTy::Mutable, None), path, "mutability")
+                } else if path.is_ident(IMMUTABLE) {
+                    set_once(&mut result.mutability, (MutabilityTy::Immutable, None), path, "mutability")
+                } else if path.is_ident(CRATE) {
+                    set_once(&mut result.crate_path, parse_crate_path(&meta)?, path, "crate")
+                } else if let Some((slot, name)) = [
+                    (&mut result.hooks.on_add, ON_ADD),
+                    (&mut result.hooks.on_insert, ON_INSERT),
+                    (&mut result.hooks.on_replace, ON_REPLACE),
+                    (&mut result.hooks.on_remove, ON_REMOVE),
+                    (&mut result.hooks.on_despawn, ON_DESPAWN),
+                ]
+                .into_iter()
+                .find(|(_, name)| path.is_ident(name))
+                {
+                    let hook = meta.value()?.parse::<Expr>()?;
+                    set_once(slot, hook, path, &format!("{name} hook"))
+                } else if path.is_ident(REQUIRE) {
+                    parse_requires(meta.input, &mut result.requires, &mut required)
+                } else if path.is_ident(CLONE_BEHAVIOR) {
+                    let clone_behavior = meta.value()?.parse::<Expr>()?;
+                    set_once(&mut result.clone_behavior, clone_behavior, path, "clone behavior")
+                } else if path.is_ident(TRANSITIONS) || path.is_ident(STRICT_TRANSITIONS) {
+                    let content;
+                    parenthesized!(content in meta.input);
+                    let rules = content.parse_terminated(Transition::parse, Token![,])?.into_iter().collect();
+                    let strict = path.is_ident(STRICT_TRANSITIONS).then(|| path.span());
+                    let transitions = Transitions { strict, rules };
+                    set_once(&mut result.transitions, transitions, path, "transitions")
+                } else {
+                    Err(unknown_attr(path, ENUM_ATTRS))
+                }
+            })?;
+        }
+
+        Ok(result)
+    }
+}
+
+/// Parses the `#[enum_component(...)]` attributes of the derives that accept nothing but `crate = ..`,
+/// reporting any other option on its own path.
+pub(crate) fn parse_crate_only(attrs: &[Attribute], derive: &str) -> syn::Result<Option<Path>> {
+    let mut crate_path = None;
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident(ENUM_COMPONENT)) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(CRATE) {
+                set_once(&mut crate_path, parse_crate_path(&meta)?, &meta.path, "crate")
+            } else {
+                let message = format!("`{derive}` only accepts #[enum_component({CRATE} = ..)]");
+                Err(syn::Error::new(meta.path.span(), message))
+            }
+        })?;
+    }
+
+    Ok(crate_path)
+}
+
+/// The path of `crate = path::to::bevy_ecs_enum_filter`, also accepted as a string literal.
+fn parse_crate_path(meta: &ParseNestedMeta) -> syn::Result<Path> {
+    let value = meta.value()?;
+    match value.peek(LitStr) {
+        true => value.parse::<LitStr>()?.parse::<Path>(),
+        false => value.parse::<Path>(),
+    }
+}
+
+/// Everything given through `#[enum_component(...)]` on a single variant.
+#[derive(Default)]
+pub(crate) struct VariantAttrs {
+    /// Inserted when an entity enters the variant and removed when it leaves.
+    pub(crate) requires: Vec<Require>,
+    /// A `fn(&Enum) -> impl Bundle` evaluated when an entity enters the variant,
+    /// whose components are removed when it leaves.
+    pub(crate) bundle: Option<Expr>,
+}
+
+impl VariantAttrs {
+    pub(crate) fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut result = Self::default();
+        let mut required = HashSet::new();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident(ENUM_COMPONENT)) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident(REQUIRE) {
+                    parse_requires(meta.input, &mut result.requires, &mut required)
+                } else if meta.path.is_ident(BUNDLE) {
+                    let bundle = meta.value()?.parse::<Expr>()?;
+                    set_once(&mut result.bundle, bundle, &meta.path, "bundle")
+                } else {
+                    Err(unknown_attr(&meta.path, VARIANT_ATTRS))
+                }
+            })?;
+        }
+
+        Ok(result)
+    }
+}
+
+/// Everything given through `#[enum_component(...)]` on a field of a variant.
+#[derive(Default)]
+pub(crate) struct FieldAttrs {
+    /// The field holds entities that are remapped by `Component::map_entities`.
+    /// The markers carry no data, so only the enum itself is remapped.
+    pub(crate) entities: bool,
+}
+
+impl FieldAttrs {
+    pub(crate) fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut result = Self::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident(ENUM_COMPONENT)) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident(ENTITIES) {
+                    match result.entities {
+                        true => Err(syn::Error::new(meta.path.span(), "duplicate entities for EnumComponent")),
+                        false => {
+                            result.entities = true;
+                            Ok(())
+                        }
+                    }
+                } else {
+                    Err(unknown_attr(&meta.path, FIELD_ATTRS))
+                }
+            })?;
+        }
+
+        Ok(result)
+    }
+}
+
+/// The transitions allowed between variants, given by `transitions(A -> B, _ -> A)`.
+/// With `strict_transitions(..)`, an illegal transition is reverted instead of reported.
+pub(crate) struct Transitions {
+    /// The span of `strict_transitions`, which clones the replaced values.
+    pub(crate) strict: Option<Span>,
+    pub(crate) rules: Vec<Transition>,
+}
+
+/// `from -> to`, where either side is a variant or `_` for any variant.
+pub(crate) struct Transition {
+    pub(crate) from: Option<Ident>,
+    pub(crate) to: Option<Ident>,
+}
+
+impl Parse for Transition {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let side = |input: ParseStream| match input.peek(Token![_]) {
+            true => input.parse::<Token![_]>().map(|_| None),
+            false => input.parse::<Ident>().map(Some),
+        };
+
+        let from = side(input)?;
+        input.parse::<Token![->]>()?;
+        let to = side(input)?;
+
+        Ok(Transition { from, to })
+    }
+}
+
+/// A required component, written the same way as in bevy's `#[require(...)]`:
+/// `Type`, `Type = expr`, `Type(..)`, `Type { .. }`, `Enum::Variant` or `Type::constructor(..)`.
+///
+/// Every form but `Type` is an expression, whose type the generated code leaves to inference. As in bevy,
+/// a bare path is taken for `Enum::Variant` when its last two segments are capitalized, and for a type
+/// otherwise; `Type = path` is never ambiguous.
+pub(crate) struct Require {
+    /// The path as written, which is the type of the component for `Type` and `Type = expr`.
+    pub(crate) path: Path,
+    value: Option<TokenStream>,
+}
+
+impl Require {
+    /// An expression evaluating to the required component.
+    pub(crate) fn value(&self) -> TokenStream {
+        let path = &self.path;
+        match &self.value {
+            Some(value) => value.clone(),
+            None => quote!(<#path as ::core::default::Default>::default()),
+        }
+    }
+}
+
+impl Parse for Require {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = input.parse::<Path>()?;
+
+        let value = if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            let expr = input.parse::<Expr>()?;
+            Some(quote!({ let value: #path = #expr; value }))
+        } else if input.peek(Brace) {
+            let content;
+            braced!(content in input);
+            let content = content.parse::<TokenStream>()?;
+            Some(quote!(#path { #content }))
+        } else if input.peek(Paren) {
+            let content;
+            parenthesized!(content in input);
+            let content = content.parse::<TokenStream>()?;
+            Some(quote!(#path(#content)))
+        } else {
+            let mut initials = path
+                .segments
+                .iter()
+                .rev()
+                .take(2)
+                .filter_map(|segment| segment.ident.to_string().chars().next());
+            let is_variant = path.segments.len() > 1 && initials.all(char::is_uppercase);
+            is_variant.then(|| quote!(#path))
+        };
+
+        Ok(Require { path, value })
+    }
+}
+
+fn parse_requires(
+    input: ParseStream, requires: &mut Vec<Require>, required: &mut HashSet<String>,
+) -> syn::Result<()> {
+    let content;
+    parenthesized!(content in input);
+
+    for require in content.parse_terminated(Require::parse, Token![,])? {
+        if !required.insert(require.path.to_token_stream().to_string()) {
+            return Err(syn::Error::new(require.path.span(), "duplicate required component for EnumComponent"));
+        }
+        requires.push(require);
+    }
+
+    Ok(())
+}
+
+fn set_once<T>(slot: &mut Option<T>, value: T, path: &Path, name: &str) -> syn::Result<()> {
+    match slot {
+        Some(_) => Err(syn::Error::new(path.span(), format!("duplicate {name} for EnumComponent"))),
+        None => {
+            *slot = Some(value);
+            Ok(())
+        }
+    }
+}
+
+fn unknown_attr(path: &Path, expected: &[&str]) -> syn::Error {
+    let expected = expected.iter().map(|attr| format!("`{attr}`")).collect::<Vec<_>>().join(", ");
+    let name = path
+        .get_ident()
+        .map(ToString::to_string)
+        .unwrap_or_else(|| quote::quote!(#path).to_string());
+    syn::Error::new(
+        path.span(),
+        format!("unknown EnumComponent attribute `{name}`, expected one of {expected}"),
+    )
+}
+
+/// Accepts any path ending in `StorageType::Table` or `StorageType::SparseSet`.
+fn parse_storage_type(value: &Path) -> syn::Result<StorageTy> {
+    match last_ident(value).as_deref() {
+        Some("Table") => Ok(StorageTy::Table),
+        Some("SparseSet") => Ok(StorageTy::SparseSet),
+        _ => Err(syn::Error::new(
+            last_span(value),
+            "invalid storage type, expected `StorageType::Table` or `StorageType::SparseSet`",
+        )),
+    }
+}
+
+/// Accepts any path ending in `Mutable` or `Immutable`.
+fn parse_mutability(value: &Path) -> syn::Result<MutabilityTy> {
+    match last_ident(value).as_deref() {
+        Some("Mutable") => Ok(MutabilityTy::Mutable),
+        Some("Immutable") => Ok(MutabilityTy::Immutable),
+        _ => Err(syn::Error::new(last_span(value), "invalid mutability, expected `Mutable` or `Immutable`")),
+    }
+}
+
+fn last_ident(path: &Path) -> Option<String> {
+    path.segments
+        .last()
+        .filter(|segment| segment.arguments.is_none())
+        .map(|segment| segment.ident.to_string())
+}
+
+fn last_span(path: &Path) -> Span {
+    path.segments.last().map_or_else(|| path.span(), |segment| segment.span())
+}
@@ -0,0 +1,140 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    token::Paren,
+    Path, Token,
+};
+
+/// The input of `Enum!`: a single variant, or a comma-separated list of filters.
+pub(crate) struct EnumInput {
+    filters: Punctuated<Filter, Token![,]>,
+    /// Whether the input starts with a parenthesis, as in `Enum!((Foo::A))`, which is a filter.
+    parenthesized: bool,
+}
+
+/// A query filter over variants, in the grammar of the runtime `VariantFilter`: variants combined with
+/// `!`, `&`, `|` and parentheses, `!` binding tighter than `&`, which binds tighter than `|`.
+/// A comma binds loosest and means the same as `&`.
+pub(crate) enum Filter {
+    Variant(Path),
+    Not(Box<Filter>),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+}
+
+impl EnumInput {
+    /// The single variant of `Enum!(Foo::A)`, which expands to the marker itself.
+    ///
+    /// A list is a filter even with a single variant and a trailing comma, as in `Enum!(Foo::A,)`.
+    pub(crate) fn variant(&self) -> Option<&Path> {
+        match self.filters.first() {
+            Some(Filter::Variant(path))
+                if self.filters.len() == 1 && !self.filters.trailing_punct() && !self.parenthesized =>
+            {
+                Some(path)
+            }
+            _ => None,
+        }
+    }
+
+    /// The filter of every other input, the list being a tuple of filters.
+    pub(crate) fn filter(self) -> Filter {
+        let mut filters = self.filters.into_iter().collect::<Vec<_>>();
+        match filters.len() {
+            1 => filters.remove(0),
+            _ => Filter::And(filters),
+        }
+    }
+}
+
+impl Parse for EnumInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.is_empty() {
+            return Err(input.error("expected a valid enum expression (i.e. `Foo::Bar`)"));
+        }
+        let parenthesized = input.peek(Paren);
+        let filters = Punctuated::parse_terminated(input)?;
+        Ok(EnumInput { filters, parenthesized })
+    }
+}
+
+impl Filter {
+    /// Expands to `With`, `Without`, `Or` and tuples, with `marker` resolving each variant to its marker.
+    ///
+    /// Bevy has no negation of a filter, so `!` is pushed down to the variants:
+    /// `!(A, B)` becomes `Or<(Without<A>, Without<B>)>` and `!(A | B)` becomes `(Without<A>, Without<B>)`.
+    pub(crate) fn expand(
+        &self, bevy_ecs: &TokenStream, negated: bool, marker: &impl Fn(&Path) -> syn::Result<TokenStream>,
+    ) -> syn::Result<TokenStream> {
+        let expand_all = |filters: &[Filter]| {
+            filters
+                .iter()
+                .map(|filter| filter.expand(bevy_ecs, negated, marker))
+                .collect::<syn::Result<Vec<_>>>()
+        };
+
+        Ok(match self {
+            Filter::Variant(path) => {
+                let marker = marker(path)?;
+                match negated {
+                    true => quote!(#bevy_ecs::query::Without<#marker>),
+                    false => quote!(#bevy_ecs::query::With<#marker>),
+                }
+            }
+            Filter::Not(filter) => filter.expand(bevy_ecs, !negated, marker)?,
+            Filter::And(filters) | Filter::Or(filters) => {
+                let filters = expand_all(filters)?;
+                match matches!(self, Filter::Or(_)) != negated {
+                    true => quote!(#bevy_ecs::query::Or<(#(#filters,)*)>),
+                    false => quote!((#(#filters,)*)),
+                }
+            }
+        })
+    }
+
+    fn parse_and(input: ParseStream) -> syn::Result<Self> {
+        let mut filters = vec![Self::parse_unary(input)?];
+        while input.peek(Token![&]) {
+            input.parse::<Token![&]>()?;
+            filters.push(Self::parse_unary(input)?);
+        }
+
+        Ok(match filters.len() {
+            1 => filters.remove(0),
+            _ => Filter::And(filters),
+        })
+    }
+
+    fn parse_unary(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(Token![!]) {
+            input.parse::<Token![!]>()?;
+            return Self::parse_unary(input).map(|filter| Filter::Not(Box::new(filter)));
+        }
+
+        if input.peek(Paren) {
+            let content;
+            parenthesized!(content in input);
+            return content.parse::<EnumInput>().map(EnumInput::filter);
+        }
+
+        input.parse::<Path>().map(Filter::Variant)
+    }
+}
+
+impl Parse for Filter {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut filters = vec![Self::parse_and(input)?];
+        while input.peek(Token![|]) {
+            input.parse::<Token![|]>()?;
+            filters.push(Self::parse_and(input)?);
+        }
+
+        Ok(match filters.len() {
+            1 => filters.remove(0),
+            _ => Filter::Or(filters),
+        })
+    }
+}
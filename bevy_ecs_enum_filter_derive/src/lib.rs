@@ -1,10 +1,19 @@
+mod attrs;
+mod filter;
+
+use attrs::{EnumAttrs, FieldAttrs, MutabilityTy, Require, StorageTy, Transitions, VariantAttrs};
 use convert_case::{Case, Casing};
+use filter::EnumInput;
 use proc_macro::TokenStream;
 use proc_macro2::Ident;
 use proc_macro_crate::{crate_name, FoundCrate};
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, quote_spanned};
 use syn::{
-    parse_macro_input, punctuated::Punctuated, spanned::Spanned, Data, DeriveInput, Pat, Path, PathSegment, Token,
+    parse_macro_input, parse_quote,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    visit_mut::{self, VisitMut},
+    Data, DataEnum, DeriveInput, Fields, Generics, Path, PathSegment, Token, Visibility,
 };
 
 /// Derive the `EnumComponent` trait on the given enum.
@@ -29,363 +38,673 @@ use syn::{
 /// We would end up generating the module `foo_filters` which contains the markers `Bar` and `Baz`.
 ///
 /// See the [`Enum!`] macro for how to properly use this generated module.
+///
+/// If `bevy_ecs_enum_filter` is only reachable through a re-export, point the derive at it with
+/// `#[enum_component(crate = path::to::bevy_ecs_enum_filter)]`. The generated code then reaches
+/// `bevy_ecs` through that path, so neither `bevy` nor `bevy_ecs` has to be in your `Cargo.toml`.
 #[proc_macro_derive(EnumComponent, attributes(enum_component))]
 pub fn derive_enum_component(item: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(item as DeriveInput);
-    let span = ast.span();
-    let DeriveInput { attrs: _, vis, ident, generics, data } = &ast;
+    let DeriveInput { attrs, vis, ident, generics, data } = &ast;
 
     let data = match data {
         Data::Enum(data) => data,
         Data::Struct(data) => {
-            return syn::Error::new(data.struct_token.span, "Cannot derive `EnumTrait` on struct type")
+            return syn::Error::new(data.struct_token.span, "Cannot derive `EnumComponent` on struct type")
                 .into_compile_error()
                 .into();
         }
         Data::Union(data) => {
-            return syn::Error::new(data.union_token.span, "Cannot derive `EnumTrait` on union type")
+            return syn::Error::new(data.union_token.span, "Cannot derive `EnumComponent` on union type")
                 .into_compile_error()
                 .into();
         }
     };
 
     let mod_ident = get_mod_ident(ident);
-    let bevy_ecs_enum_filter = get_crate("bevy_ecs_enum_filter");
-    #[cfg(not(feature = "ambiguous_import"))]
-    let bevy = {
-        #[cfg(not(feature = "bevy"))]
-        {
-            get_crate("bevy_ecs")
-        }
-        #[cfg(feature = "bevy")]
-        {
-            get_crate("bevy")
-        }
-    };
 
-    const ATTR_STORAGE_TYPE: &str = "storage_type";
-    const ATTR_MUTABILITY: &str = "mutability";
-
-    let attrs = match parse_attrs(&ast) {
-        Ok(list) => {
-            match list.iter().find(|source| {
-                !source.source_type.is_ident(ATTR_STORAGE_TYPE) && !source.source_type.is_ident(ATTR_MUTABILITY)
-            }) {
-                Some(source) => {
-                    return syn::Error::new(
-                        span,
-                        format!(
-                            r#""{}" is not a correct attribute path for EnumComponent
-Available path = ["{ATTR_STORAGE_TYPE}, "{ATTR_MUTABILITY}""]
-Check for typos"#,
-                            source.source_type.get_ident().unwrap()
-                        ),
-                    )
-                    .into_compile_error()
-                    .into()
-                }
-                None => list,
-            }
-        }
+    let attrs = match EnumAttrs::parse(attrs) {
+        Ok(attrs) => attrs,
         Err(e) => return e.into_compile_error().into(),
     };
 
-    let default_storage_type = || {
-        #[cfg(feature = "ambiguous_import")]
-        {
-            quote!(StorageType::Table)
-        }
-        #[cfg(all(not(feature = "ambiguous_import"), not(feature = "bevy")))]
-        {
-            quote!(#bevy::component::StorageType::Table)
-        }
-        #[cfg(all(not(feature = "ambiguous_import"), feature = "bevy"))]
-        {
-            quote!(#bevy::ecs::component::StorageType::Table)
-        }
+    let (bevy_ecs_enum_filter, bevy_ecs) = match resolve_crates(ident, attrs.crate_path.as_ref()) {
+        Ok(paths) => paths,
+        Err(e) => return e.into_compile_error().into(),
     };
 
-    let storage_type = match attrs.is_empty() {
-        true => default_storage_type(),
-        false => {
-            let mut filtered = attrs
-                .iter()
-                .filter(|source| source.source_type.is_ident("storage_type"))
-                .collect::<Vec<_>>();
-
-            if 1 < filtered.len() {
-                return syn::Error::new(ast.span(), "Only one storage_type is allowed for EnumComponent")
-                    .into_compile_error()
-                    .into();
-            }
+    // a path written by the user is kept as-is, so that it still resolves (and its imports stay used)
+    let storage_type = match &attrs.storage_type {
+        Some((_, Some(path))) => quote!(#path),
+        Some((StorageTy::SparseSet, None)) => quote!(#bevy_ecs::component::StorageType::SparseSet),
+        Some((StorageTy::Table, None)) | None => quote!(#bevy_ecs::component::StorageType::Table),
+    };
 
-            match filtered.pop() {
-                Some(source) => {
-                    let pat = &source.source_value;
-                    quote!(#pat)
-                }
-                None => default_storage_type(),
-            }
-        }
+    let mutability = match &attrs.mutability {
+        Some((_, Some(path))) => quote!(#path),
+        Some((MutabilityTy::Immutable, None)) => quote!(#bevy_ecs::component::Immutable),
+        Some((MutabilityTy::Mutable, None)) | None => quote!(#bevy_ecs::component::Mutable),
     };
 
-    let default_mutability = || {
-        #[cfg(feature = "ambiguous_import")]
-        {
-            quote!(Mutable)
-        }
-        #[cfg(all(not(feature = "ambiguous_import"), not(feature = "bevy")))]
-        {
-            quote!(#bevy::component::Mutable)
-        }
-        #[cfg(all(not(feature = "ambiguous_import"), feature = "bevy"))]
-        {
-            quote!(#bevy::ecs::component::Mutable)
-        }
+    let variants = data.variants.iter().map(|variant| &variant.ident).collect::<Vec<_>>();
+
+    let (impl_generics, ty_generics, where_clause) = &generics.split_for_impl();
+
+    let variant_attrs = match data
+        .variants
+        .iter()
+        .map(|variant| VariantAttrs::parse(&variant.attrs))
+        .collect::<syn::Result<Vec<_>>>()
+    {
+        Ok(list) => list,
+        Err(e) => return e.into_compile_error().into(),
     };
 
-    let mutability = match attrs.is_empty() {
-        true => default_mutability(),
-        false => {
-            let mut filtered = attrs
-                .iter()
-                .filter(|source| source.source_type.is_ident("mutability"))
-                .collect::<Vec<_>>();
-
-            if 1 < filtered.len() {
-                return syn::Error::new(ast.span(), "Only one mutability is allowed for EnumComponent")
-                    .into_compile_error()
-                    .into();
+    let patterns = data
+        .variants
+        .iter()
+        .map(|variant| {
+            let head = &variant.ident;
+            match variant.fields {
+                Fields::Named(_) => quote!(#ident::#head {..}),
+                Fields::Unnamed(_) => quote!(#ident::#head (..)),
+                Fields::Unit => quote!(#ident::#head),
             }
+        })
+        .collect::<Vec<_>>();
 
-            match filtered.pop() {
-                Some(source) => {
-                    let pat = &source.source_value;
-                    quote!(#pat)
-                }
-                None => default_mutability(),
-            }
-        }
-    };
+    let indices = (0..variants.len()).collect::<Vec<_>>();
 
-    let variants = data.variants.iter().map(|variant| &variant.ident).collect::<Vec<_>>();
+    // every component required by a variant gets a bit in `EnumState::required`, set while the hooks own it
+    if let Some(require) = variant_attrs.iter().flat_map(|attrs| &attrs.requires).nth(u64::BITS as usize) {
+        let message =
+            format!("an enum component supports at most {} components required by its variants", u64::BITS);
+        return syn::Error::new_spanned(&require.path, message).into_compile_error().into();
+    }
+    let required_bits = variant_attrs
+        .iter()
+        .scan(0usize, |offset, attrs| {
+            let bits = (*offset..*offset + attrs.requires.len()).collect::<Vec<_>>();
+            *offset += attrs.requires.len();
+            Some(bits)
+        })
+        .collect::<Vec<_>>();
 
-    let docs = variants.iter().map(|variant| {
-        format!("Marker component generated for [`{}::{}`][super::{}::{}]", ident, variant, ident, variant)
+    // entering a variant inserts its marker, its bundle and the components it requires, unless already present;
+    // the bundle is built from the borrowed value right away, so the enum never has to be cloned
+    let (on_insert, on_replace) = (attrs::ON_INSERT, attrs::ON_REPLACE);
+    let variant_code = patterns.iter().zip(&variants).zip(&variant_attrs).zip(&required_bits);
+    let inner_insert = variant_code.clone().map(|(((pattern, head), attrs), bits)| {
+        let required = (!attrs.requires.is_empty()).then(|| {
+            let values = attrs.requires.iter().map(Require::value);
+            quote! {
+                let mut required = 0;
+                #(if #bevy_ecs_enum_filter::__private::insert_required(&mut entity_mut, || #values) {
+                    required |= 1u64 << #bits;
+                })*
+                #bevy_ecs_enum_filter::__private::own_required::<#ident>(&mut entity_mut, required);
+            }
+        });
+        let (value, bundle, insert_bundle) = match &attrs.bundle {
+            Some(bundle) => (
+                quote!(value @),
+                Some(quote!(let bundle = (#bundle)(value);)),
+                Some(quote!(entity_mut.insert(bundle);)),
+            ),
+            None => (quote!(), None, None),
+        };
+        quote! {
+            Some(#value #pattern) => {
+                #bundle
+                world.commands().queue(move |world: &mut #bevy_ecs::world::World| {
+                    let mut entity_mut =
+                        #bevy_ecs_enum_filter::__private::entity_mut::<#ident>(world, entity, #on_insert)?;
+                    entity_mut.insert(#mod_ident::#head);
+                    #required
+                    #insert_bundle
+                    Ok::<(), #bevy_ecs_enum_filter::EnumFilterError>(())
+                });
+            }
+        }
     });
 
-    let mod_doc = format!(
-        "Auto-generated module containing marker components for each variant of [`{}`][super::{}]",
-        ident, ident
-    );
+    // leaving a variant removes its marker, its bundle and the components it requires that entering it inserted,
+    // unless the entity is back in the same variant, or the transition turned out illegal and is being reverted
+    let inner_remove = variant_code.zip(&indices).map(|((((pattern, head), attrs), bits), index)| {
+        let required = (!attrs.requires.is_empty()).then(|| {
+            let values = attrs.requires.iter().map(Require::value);
+            let mask = bits.iter().fold(0u64, |mask, bit| mask | 1 << bit);
+            quote! {
+                let required =
+                    #bevy_ecs_enum_filter::__private::take_required::<#ident>(&mut entity_mut, #index, #mask);
+                #(if required & 1u64 << #bits != 0 {
+                    #bevy_ecs_enum_filter::__private::remove_required(&mut entity_mut, || #values);
+                })*
+            }
+        });
+        let bundle = attrs.bundle.as_ref().map(
+            |bundle| quote!(#bevy_ecs_enum_filter::__private::remove_variant_bundle(&mut entity_mut, #bundle);),
+        );
+        quote! {
+            Some(#pattern) => {
+                world.commands().queue(move |world: &mut #bevy_ecs::world::World| {
+                    if #bevy_ecs_enum_filter::__private::reverting::<#ident>(world, entity) {
+                        return Ok(());
+                    }
+                    let mut entity_mut =
+                        #bevy_ecs_enum_filter::__private::entity_mut::<#ident>(world, entity, #on_replace)?;
+                    entity_mut.remove::<#mod_ident::#head>();
+                    #required
+                    #bundle
+                    Ok::<(), #bevy_ecs_enum_filter::EnumFilterError>(())
+                });
+            }
+        }
+    });
 
-    let (impl_generics, ty_generics, where_clause) = &generics.split_for_impl();
+    let map_entities = match map_entities_arms(ident, data, &bevy_ecs) {
+        Ok(arms) if arms.is_empty() => None,
+        Ok(arms) => Some(quote! {
+            fn map_entities<M: #bevy_ecs::entity::EntityMapper>(this: &mut Self, mapper: &mut M) {
+                #[allow(unreachable_patterns)]
+                match this {
+                    #(#arms)*
+                    _ => {}
+                }
+            }
+        }),
+        Err(e) => return e.into_compile_error().into(),
+    };
 
-    let inner_insert = data.variants.iter().fold(vec![], |mut list, variant| {
-        let head = &variant.ident;
-        list.push(match variant.fields {
-            syn::Fields::Named(_) => quote! {
-                #ident::#head {..} => entity_mut.insert(#mod_ident::#head)
-            },
-            syn::Fields::Unnamed(_) => quote! {
-                #ident::#head (_) => entity_mut.insert(#mod_ident::#head)
-            },
-            syn::Fields::Unit => quote! {
-                #ident::#head => entity_mut.insert(#mod_ident::#head)
-            },
-        });
+    let clone_behavior = match &attrs.clone_behavior {
+        Some(behavior) => quote!(#bevy_ecs::component::ComponentCloneBehavior::#behavior),
+        None => quote! {
+            use #bevy_ecs::component::{DefaultCloneBehaviorBase, DefaultCloneBehaviorViaClone};
+            (&&&#bevy_ecs::component::DefaultCloneBehaviorSpecialization::<Self>::default()).default_clone_behavior()
+        },
+    };
 
-        list
+    let register_required = attrs.requires.iter().map(|require| {
+        let value = require.value();
+        quote!(required_components.register_required(|| #value);)
     });
 
-    let inner_remove = data.variants.iter().fold(vec![], |mut list, variant| {
-        let head = &variant.ident;
-        list.push(match variant.fields {
-            syn::Fields::Named(_) => quote! {
-                #ident::#head {..} => cmd.remove::<#mod_ident::#head>()
-            },
-            syn::Fields::Unnamed(_) => quote! {
-                #ident::#head (_) => cmd.remove::<#mod_ident::#head>()
-            },
-            syn::Fields::Unit => quote! {
-                #ident::#head => cmd.remove::<#mod_ident::#head>()
+    // with strict transitions, the replaced value is cloned to be inserted back if the transition is illegal;
+    // the user's `on_insert` and `on_replace` skip the illegal value, so they only see it replaced by itself
+    let strict = attrs.transitions.as_ref().and_then(|transitions| transitions.strict);
+    let stash = strict
+        .map(|span| quote_spanned!(span=> #bevy_ecs_enum_filter::__private::stash::<#ident>(&mut world, entity);));
+
+    // each generated hook updates its bookkeeping and queues the marker command first, then calls the user's hook
+    // of the same name, whose own commands therefore see the markers up to date
+    let hook = |name: &str, body: Option<proc_macro2::TokenStream>, user: &Option<syn::Expr>| {
+        let skips_reverted = strict.is_some() && (name == attrs::ON_INSERT || name == attrs::ON_REPLACE);
+        let name = format_ident!("{}", name);
+        let world = match body {
+            Some(_) => quote!(mut world),
+            None => quote!(world),
+        };
+        let user = user.as_ref().map(|user| match skips_reverted {
+            true => quote! {
+                if !#bevy_ecs_enum_filter::__private::reverting::<#ident>(&world, context.entity) {
+                    (#user)(world, context);
+                }
             },
+            false => quote!((#user)(world, context);),
         });
 
-        list
-    });
-
-    #[cfg(all(not(feature = "bevy"), not(feature = "ambiguous_import")))]
-    let impl_component = quote! {
-            impl #impl_generics #bevy::component::Component for #ident #ty_generics #where_clause {
-                const STORAGE_TYPE: #bevy::component::StorageType = #storage_type;
-                type Mutability = #mutability;
-
-                fn on_insert() -> Option<#bevy::lifecycle::ComponentHook> {
-                    Some(|mut world, #bevy::lifecycle::HookContext { entity, .. }| {
-                        let enum_comp = world.get::<#ident>(entity).unwrap().clone();
-                        let mut cmd = world.commands();
-                        cmd.queue(move |world: &mut #bevy::prelude::World| {
-                            let mut entity_mut = world.entity_mut(entity);
-                            match enum_comp {
-                                #(#inner_insert),*
-                            };
-                        })
+        (body.is_some() || user.is_some()).then(|| {
+            quote! {
+                fn #name() -> Option<#bevy_ecs::lifecycle::ComponentHook> {
+                    Some(|#world, context: #bevy_ecs::lifecycle::HookContext| {
+                        #body
+                        #user
                     })
                 }
-                fn on_replace() -> Option<#bevy::lifecycle::ComponentHook> {
-                    Some(|mut world, #bevy::lifecycle::HookContext { entity, .. }| {
-                        let enum_comp = world.get::<#ident>(entity).unwrap().clone();
-                        let mut cmd = world.commands();
-                        let mut cmd = cmd.entity(entity);
-                        match enum_comp {
-                            #(#inner_remove),*
-                        };
-                    })
+            }
+        })
+    };
+
+    // The bookkeeping of the hooks is updated right away, while the markers are maintained by a command.
+    // Markers go away with a despawned entity, so there is nothing to queue while despawning,
+    // and a command that runs after a despawn reports it.
+    // `modify_variant` keeps the variant, so neither `on_insert` nor `on_replace` has anything to do then,
+    // and neither has the illegal value of a strict transition, replaced by the reverted one.
+    let on_add = hook(attrs::ON_ADD, None, &attrs.hooks.on_add);
+    let on_insert = hook(
+        attrs::ON_INSERT,
+        Some(quote! {
+            {
+                let entity = context.entity;
+                match world.get::<#ident>(entity).map(#bevy_ecs_enum_filter::EnumComponent::variant_index) {
+                    Some(index) if #bevy_ecs_enum_filter::__private::enter::<#ident>(&mut world, entity, index) => {
+                        match world.get::<#ident>(entity) {
+                            #(#inner_insert)*
+                            None => {}
+                        }
+                    }
+                    Some(_) => {}
+                    None => #bevy_ecs_enum_filter::__private::missing_component::<#ident>(&world, entity, #on_insert),
                 }
-                fn on_remove() -> Option<#bevy::lifecycle::ComponentHook> {
-                    Some(|mut world, #bevy::lifecycle::HookContext { entity, .. }| {
-                        let enum_comp = world.get::<#ident>(entity).unwrap().clone();
-                        let mut cmd = world.commands();
-                        let mut cmd = cmd.entity(entity);
-                        match enum_comp {
-                            #(#inner_remove),*
-                        };
-                    })
+            }
+        }),
+        &attrs.hooks.on_insert,
+    );
+    // `on_replace` also runs before `on_remove`, so it is the one that leaves the variant
+    let on_replace = hook(
+        attrs::ON_REPLACE,
+        Some(quote! {
+            {
+                let entity = context.entity;
+                let (despawning, modifying) = world
+                    .get::<#bevy_ecs_enum_filter::__private::EnumState<#ident>>(entity)
+                    .map_or((false, false), |state| (state.despawning, state.modifying));
+                let reverting = #bevy_ecs_enum_filter::__private::reverting::<#ident>(&world, entity);
+                match world.get::<#ident>(entity).map(#bevy_ecs_enum_filter::EnumComponent::variant_index) {
+                    Some(_) if modifying || reverting => {}
+                    Some(index) => {
+                        #stash
+                        {
+                            use #bevy_ecs_enum_filter::__private::{KeepClone, KeepNothing};
+                            (&#bevy_ecs_enum_filter::__private::KeepReplaced::<#ident>::default()).keep(&mut world, entity);
+                        }
+                        #bevy_ecs_enum_filter::__private::exit::<#ident>(&mut world, entity, index);
+                        if !despawning {
+                            match world.get::<#ident>(entity) {
+                                #(#inner_remove)*
+                                None => {}
+                            }
+                        }
+                    }
+                    None => #bevy_ecs_enum_filter::__private::missing_component::<#ident>(&world, entity, #on_replace),
                 }
             }
-    };
+        }),
+        &attrs.hooks.on_replace,
+    );
+    let on_remove = hook(
+        attrs::ON_REMOVE,
+        Some(quote!(#bevy_ecs_enum_filter::__private::remove::<#ident>(&mut world, context.entity);)),
+        &attrs.hooks.on_remove,
+    );
+    let on_despawn = hook(
+        attrs::ON_DESPAWN,
+        Some(quote! {
+            if let Some(mut state) = world.get_mut::<#bevy_ecs_enum_filter::__private::EnumState<#ident>>(context.entity) {
+                state.despawning = true;
+            }
+        }),
+        &attrs.hooks.on_despawn,
+    );
 
-    #[cfg(all(feature = "bevy", not(feature = "ambiguous_import")))]
     let impl_component = quote! {
-            impl #impl_generics #bevy::ecs::component::Component for #ident #ty_generics #where_clause {
-                const STORAGE_TYPE: #bevy::ecs::component::StorageType = #storage_type;
+            impl #impl_generics #bevy_ecs::component::Component for #ident #ty_generics #where_clause {
+                const STORAGE_TYPE: #bevy_ecs::component::StorageType = #storage_type;
                 type Mutability = #mutability;
 
-                fn on_insert() -> Option<#bevy::ecs::lifecycle::ComponentHook> {
-                    Some(|mut world, #bevy::ecs::lifecycle::HookContext { entity, .. }| {
-                        let enum_comp = world.get::<#ident>(entity).unwrap().clone();
-                        let mut cmd = world.commands();
-                        cmd.queue(move |world: &mut #bevy::prelude::World| {
-                            let mut entity_mut = world.entity_mut(entity);
-                            match enum_comp {
-                                #(#inner_insert),*
-                            };
-                        })
-                    })
+                fn register_required_components(
+                    _requiree: #bevy_ecs::component::ComponentId,
+                    required_components: &mut #bevy_ecs::component::RequiredComponentsRegistrator,
+                ) {
+                    required_components.register_required::<#bevy_ecs_enum_filter::__private::EnumState<Self>>(Default::default);
+                    #(#register_required)*
                 }
-                fn on_replace() -> Option<#bevy::ecs::lifecycle::ComponentHook> {
-                    Some(|mut world, #bevy::ecs::lifecycle::HookContext { entity, .. }| {
-                        let enum_comp = world.get::<#ident>(entity).unwrap().clone();
-                        let mut cmd = world.commands();
-                        let mut cmd = cmd.entity(entity);
-                        match enum_comp {
-                            #(#inner_remove),*
-                        };
-                    })
+
+                #on_add
+                #on_insert
+                #on_replace
+                #on_remove
+                #on_despawn
+
+                fn clone_behavior() -> #bevy_ecs::component::ComponentCloneBehavior {
+                    #clone_behavior
                 }
-                fn on_remove() -> Option<#bevy::ecs::lifecycle::ComponentHook> {
-                    Some(|mut world, #bevy::ecs::lifecycle::HookContext { entity, .. }| {
-                        let enum_comp = world.get::<#ident>(entity).unwrap().clone();
-                        let mut cmd = world.commands();
-                        let mut cmd = cmd.entity(entity);
-                        match enum_comp {
-                            #(#inner_remove),*
-                        };
-                    })
+
+                #map_entities
+            }
+    };
+
+    let allows_transition =
+        match attrs.transitions.as_ref().map(|transitions| transition_rules(&variants, transitions)) {
+            Some(Ok(rules)) => Some(quote! {
+                fn allows_transition(from: usize, to: usize) -> bool {
+                    from == to #(|| matches!((from, to), #rules))*
+                }
+            }),
+            Some(Err(e)) => return e.into_compile_error().into(),
+            None => None,
+        };
+
+    let enum_name = ident.to_string();
+    let variant_names = variants.iter().map(|variant| variant.to_string());
+
+    let impl_enum_component = quote! {
+        impl #impl_generics #bevy_ecs_enum_filter::EnumComponent for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #enum_name;
+            const VARIANTS: &'static [&'static str] = &[#(#variant_names),*];
+
+            fn register_markers(world: &mut #bevy_ecs::world::World) -> Vec<#bevy_ecs::component::ComponentId> {
+                vec![#(world.register_component::<#mod_ident::#variants>()),*]
+            }
+
+            fn variant_index(&self) -> usize {
+                match self {
+                    #(#patterns => #indices,)*
                 }
             }
+
+            #allows_transition
+        }
     };
 
-    #[cfg(feature = "ambiguous_import")]
-    let impl_component = quote! {
-            impl #impl_generics Component for #ident #ty_generics #where_clause {
-                const STORAGE_TYPE: StorageType = #storage_type;
-                type Mutability = #mutability;
+    // the fields of each variant, borrowed, as yielded by `VariantRef` and `VariantMut`
+    let refs = variant_views(ident, vis, data, false);
+    let muts = variant_views(ident, vis, data, true);
+    let (ref_structs, ref_types, as_variant_arms) = (&refs.structs, &refs.types, &refs.arms);
+    let (mut_structs, mut_types, as_variant_mut_arms) = (&muts.structs, &muts.types, &muts.arms);
 
-                fn on_insert() -> Option<ComponentHook> {
-                    Some(|mut world, HookContext { entity, .. }| {
-                        let enum_comp = world.get::<#ident>(entity).unwrap().clone();
-                        let mut cmd = world.commands();
-                        cmd.queue(move |world: &mut World| {
-                            let mut entity_mut = world.entity_mut(entity);
-                            match enum_comp {
-                                #(#inner_insert),*
-                            };
-                        })
-                    })
+    // lets the markers reach the enum back
+    let impl_variant_markers = quote! {
+        #(
+            impl #bevy_ecs_enum_filter::EnumMarker for #mod_ident::#variants {
+                type Enum = #ident;
+                const INDEX: usize = #indices;
+                type Ref<'a> = #ref_types;
+
+                #[allow(unreachable_patterns)]
+                fn as_variant(value: &Self::Enum) -> Option<Self::Ref<'_>> {
+                    match value {
+                        #as_variant_arms,
+                        _ => None,
+                    }
                 }
-                fn on_replace() -> Option<ComponentHook> {
-                    Some(|mut world, HookContext { entity, .. }| {
-                        let enum_comp = world.get::<#ident>(entity).unwrap().clone();
-                        let mut cmd = world.commands();
-                        let mut cmd = cmd.entity(entity);
-                        match enum_comp {
-                            #(#inner_remove),*
-                        };
-                    })
+
+                fn shrink_ref<'long: 'short, 'short>(fields: Self::Ref<'long>) -> Self::Ref<'short> {
+                    fields
                 }
-                fn on_remove() -> Option<ComponentHook> {
-                    Some(|mut world, HookContext { entity, .. }| {
-                        let enum_comp = world.get::<#ident>(entity).unwrap().clone();
-                        let mut cmd = world.commands();
-                        let mut cmd = cmd.entity(entity);
-                        match enum_comp {
-                            #(#inner_remove),*
-                        };
-                    })
+
+                type Mut<'a> = #mut_types;
+
+                #[allow(unreachable_patterns)]
+                fn as_variant_mut(value: &mut Self::Enum) -> Option<Self::Mut<'_>> {
+                    match value {
+                        #as_variant_mut_arms,
+                        _ => None,
+                    }
+                }
+
+                fn shrink_mut<'long: 'short, 'short>(fields: Self::Mut<'long>) -> Self::Mut<'short> {
+                    fields
                 }
             }
+        )*
     };
 
-    #[cfg(feature = "ambiguous_import")]
-    {
-        TokenStream::from(quote! {
-            #impl_component
-            impl #impl_generics #bevy_ecs_enum_filter::EnumComponent for #ident #ty_generics #where_clause {}
+    let views = quote!(#(#ref_structs)* #(#mut_structs)*);
+    let marker_module = marker_module(ident, vis, generics, &variants, &bevy_ecs_enum_filter, &bevy_ecs, views);
+
+    TokenStream::from(quote! {
+        #impl_component
+        #impl_enum_component
+        #impl_variant_markers
+        #marker_module
+    })
+}
+
+/// Derive the `EnumSetComponent` trait on an enum of unit variants, to be used as flags in an `EnumSet`.
+///
+/// As with [`EnumComponent`], a `*_filters` module with a marker component for each variant is generated,
+/// and [`Enum!`] finds the markers the same way. The `EnumSet` keeps the marker of every variant it holds
+/// on the entity. The enum has at most 64 variants, and only `#[enum_component(crate = ..)]` applies to it.
+#[proc_macro_derive(EnumSetComponent, attributes(enum_component))]
+pub fn derive_enum_set_component(item: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(item as DeriveInput);
+    let DeriveInput { attrs, vis, ident, generics, data } = &ast;
+
+    let data = match data {
+        Data::Enum(data) => data,
+        Data::Struct(data) => {
+            return syn::Error::new(data.struct_token.span, "Cannot derive `EnumSetComponent` on struct type")
+                .into_compile_error()
+                .into();
+        }
+        Data::Union(data) => {
+            return syn::Error::new(data.union_token.span, "Cannot derive `EnumSetComponent` on union type")
+                .into_compile_error()
+                .into();
+        }
+    };
+
+    if let Some(variant) = data.variants.iter().find(|variant| !matches!(variant.fields, Fields::Unit)) {
+        return syn::Error::new(variant.fields.span(), "`EnumSetComponent` variants cannot have fields")
+            .into_compile_error()
+            .into();
+    }
+    if let Some(variant) = data.variants.iter().nth(64) {
+        return syn::Error::new(variant.span(), "`EnumSetComponent` supports at most 64 variants")
+            .into_compile_error()
+            .into();
+    }
+
+    let crate_path = match attrs::parse_crate_only(attrs, "EnumSetComponent") {
+        Ok(crate_path) => crate_path,
+        Err(e) => return e.into_compile_error().into(),
+    };
+
+    let (bevy_ecs_enum_filter, bevy_ecs) = match resolve_crates(ident, crate_path.as_ref()) {
+        Ok(paths) => paths,
+        Err(e) => return e.into_compile_error().into(),
+    };
+
+    let mod_ident = get_mod_ident(ident);
+    let variants = data.variants.iter().map(|variant| &variant.ident).collect::<Vec<_>>();
+    let indices = (0..variants.len()).collect::<Vec<_>>();
+    let enum_name = ident.to_string();
+    let variant_names = variants.iter().map(|variant| variant.to_string());
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let marker_module = marker_module(ident, vis, generics, &variants, &bevy_ecs_enum_filter, &bevy_ecs, quote!());
+
+    TokenStream::from(quote! {
+        impl #impl_generics #bevy_ecs_enum_filter::EnumSetComponent for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #enum_name;
+            const VARIANTS: &'static [&'static str] = &[#(#variant_names),*];
+
+            fn variant_index(&self) -> usize {
+                match self {
+                    #(#ident::#variants => #indices,)*
+                }
+            }
 
-            #[doc = #mod_doc]
-            #[doc(hidden)]
-            #vis mod #mod_ident {
+            fn from_variant_index(index: usize) -> Option<Self> {
+                match index {
+                    #(#indices => Some(#ident::#variants),)*
+                    _ => None,
+                }
+            }
+
+            fn sync_markers(entity: &mut #bevy_ecs::world::EntityWorldMut, bits: u64) {
                 #(
-                    use super::*;
-                    #[doc = #docs]
-                    #[doc(hidden)]
-                    #[derive(Component)]
-                    #[component(immutable)]
-                    pub struct #variants;
+                    match (bits & 1 << #indices != 0, entity.contains::<#mod_ident::#variants>()) {
+                        (true, false) => {
+                            entity.insert(#mod_ident::#variants);
+                        }
+                        (false, true) => {
+                            entity.remove::<#mod_ident::#variants>();
+                        }
+                        _ => {}
+                    }
                 )*
             }
-        })
+        }
+
+        #marker_module
+    })
+}
+
+/// Derive the `EnumResource` trait on an enum stored as a resource, along with `Resource` itself.
+///
+/// As with [`EnumComponent`], a `*_filters` module with a marker for each variant is generated, and
+/// [`Enum!`] finds the markers the same way. They are never inserted: they name a variant for the run
+/// conditions `in_variant`, `entered_variant` and `exited_variant`. Only `#[enum_component(crate = ..)]`
+/// applies to the enum.
+///
+/// [`EnumComponent`]: derive@EnumComponent
+#[proc_macro_derive(EnumResource, attributes(enum_component))]
+pub fn derive_enum_resource(item: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(item as DeriveInput);
+    let DeriveInput { attrs, vis, ident, generics, data } = &ast;
+
+    let data = match data {
+        Data::Enum(data) => data,
+        Data::Struct(data) => {
+            return syn::Error::new(data.struct_token.span, "Cannot derive `EnumResource` on struct type")
+                .into_compile_error()
+                .into();
+        }
+        Data::Union(data) => {
+            return syn::Error::new(data.union_token.span, "Cannot derive `EnumResource` on union type")
+                .into_compile_error()
+                .into();
+        }
+    };
+
+    let crate_path = match attrs::parse_crate_only(attrs, "EnumResource") {
+        Ok(crate_path) => crate_path,
+        Err(e) => return e.into_compile_error().into(),
+    };
+
+    let (bevy_ecs_enum_filter, bevy_ecs) = match resolve_crates(ident, crate_path.as_ref()) {
+        Ok(paths) => paths,
+        Err(e) => return e.into_compile_error().into(),
+    };
+
+    let mod_ident = get_mod_ident(ident);
+    let variants = data.variants.iter().map(|variant| &variant.ident).collect::<Vec<_>>();
+    let patterns = data.variants.iter().map(|variant| {
+        let head = &variant.ident;
+        match variant.fields {
+            Fields::Named(_) => quote!(#ident::#head {..}),
+            Fields::Unnamed(_) => quote!(#ident::#head (..)),
+            Fields::Unit => quote!(#ident::#head),
+        }
+    });
+    let indices = (0..variants.len()).collect::<Vec<_>>();
+    let enum_name = ident.to_string();
+    let variant_names = variants.iter().map(|variant| variant.to_string());
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let marker_module = marker_module(ident, vis, generics, &variants, &bevy_ecs_enum_filter, &bevy_ecs, quote!());
+
+    TokenStream::from(quote! {
+        impl #impl_generics #bevy_ecs::resource::Resource for #ident #ty_generics #where_clause {}
+
+        impl #impl_generics #bevy_ecs_enum_filter::EnumResource for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #enum_name;
+            const VARIANTS: &'static [&'static str] = &[#(#variant_names),*];
+
+            fn variant_index(&self) -> usize {
+                match self {
+                    #(#patterns => #indices,)*
+                }
+            }
+        }
+
+        #(
+            impl #bevy_ecs_enum_filter::ResourceMarker for #mod_ident::#variants {
+                type Resource = #ident;
+                const INDEX: usize = #indices;
+            }
+        )*
+
+        #marker_module
+    })
+}
+
+/// The paths to `bevy_ecs_enum_filter` and `bevy_ecs` for the code generated for the enum `ident`.
+fn resolve_crates(
+    ident: &Ident, crate_path: Option<&Path>,
+) -> syn::Result<(proc_macro2::TokenStream, proc_macro2::TokenStream)> {
+    // with `crate = path`, everything is reached through the re-exports of bevy_ecs_enum_filter
+    // so that neither bevy nor bevy_ecs has to be a dependency of the user's crate
+    match crate_path {
+        Some(path) => Ok((quote!(#path), quote!(#path::__private::bevy_ecs))),
+        None => match get_crate("bevy_ecs_enum_filter") {
+            Some(bevy_ecs_enum_filter) => {
+                let bevy_ecs = get_bevy_ecs(&bevy_ecs_enum_filter);
+                Ok((bevy_ecs_enum_filter, bevy_ecs))
+            }
+            None => Err(syn::Error::new(
+                ident.span(),
+                format!(
+                    r#"`bevy_ecs_enum_filter` is not found in Cargo.toml
+If it is re-exported by another crate, use #[enum_component({} = path::to::bevy_ecs_enum_filter)]"#,
+                    attrs::CRATE
+                ),
+            )),
+        },
     }
+}
 
-    #[cfg(not(feature = "ambiguous_import"))]
-    {
-        TokenStream::from(quote! {
-            #impl_component
-            impl #impl_generics #bevy_ecs_enum_filter::EnumComponent for #ident #ty_generics #where_clause {}
+/// The `*_filters` module with a marker component for each variant of the enum `ident` and the extra `items`,
+/// and the `VariantMarker` implementations that let `Enum!` reach the markers through any path to the enum.
+fn marker_module(
+    ident: &Ident, vis: &Visibility, generics: &Generics, variants: &[&Ident],
+    bevy_ecs_enum_filter: &proc_macro2::TokenStream, bevy_ecs: &proc_macro2::TokenStream,
+    items: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let mod_ident = get_mod_ident(ident);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-            #[doc = #mod_doc]
-            #[doc(hidden)]
-            #vis mod #mod_ident {
-                #(
-                    use super::*;
-                    #[doc = #docs]
-                    #[doc(hidden)]
-                    #[derive(#bevy::prelude::Component)]
-                    #[component(immutable)]
-                    pub struct #variants;
-                )*
+    let docs = variants.iter().map(|variant| {
+        format!("Marker component generated for [`{}::{}`][super::{}::{}]", ident, variant, ident, variant)
+    });
+
+    let mod_doc = format!(
+        "Auto-generated module containing marker components for each variant of [`{}`][super::{}]",
+        ident, ident
+    );
+
+    // `EnumMarker::Enum` names the enum, so a marker may not be more visible than the enum
+    let marker_vis = get_marker_vis(vis);
+    let keys = variants.iter().map(|variant| variant_key(&variant.to_string()));
+
+    quote! {
+        #(
+            impl #impl_generics #bevy_ecs_enum_filter::VariantMarker<#keys> for #ident #ty_generics #where_clause {
+                type Marker = #mod_ident::#variants;
             }
-        })
+        )*
+
+        #[doc = #mod_doc]
+        #[doc(hidden)]
+        #vis mod #mod_ident {
+            #(
+                use super::*;
+                #[doc = #docs]
+                #[doc(hidden)]
+                #marker_vis struct #variants;
+
+                impl #bevy_ecs::component::Component for #variants {
+                    const STORAGE_TYPE: #bevy_ecs::component::StorageType = #bevy_ecs::component::StorageType::Table;
+                    type Mutability = #bevy_ecs::component::Immutable;
+
+                    // the hooks of the cloned enum insert the marker again, exactly once
+                    fn clone_behavior() -> #bevy_ecs::component::ComponentCloneBehavior {
+                        #bevy_ecs::component::ComponentCloneBehavior::Ignore
+                    }
+                }
+            )*
+
+            #items
+        }
     }
 }
 
 /// This macro can be used to retrieve the marker component generated by the [`EnumComponent`] derive for
 /// the given enum value.
 ///
-/// Because this macro relies on the module generated by the [`EnumComponent`] derive macro, you must
-/// make sure it is in scope. Otherwise, you'll likely run into a compile error.
+/// The marker is found through the `VariantMarker` implementations of the enum, so any path to the
+/// enum works: type aliases, renamed imports, re-exports and `Self` inside an `impl` block. The
+/// generated `*_filters` module does not have to be in scope.
 ///
 /// # Example
 ///
@@ -400,84 +719,322 @@ Check for typos"#,
 /// > you do __not__ need to specify any fields. Treat all variants like a unit variant.
 ///
 /// ```ignore
-/// // Make sure everything is in scope
-/// use path::to::{Foo, foo_filters};
-/// type Marker = Enum!(Foo::Baz);
+/// use path::to::Foo as Bar;
+/// type Marker = Enum!(Bar::Baz);
+/// ```
+///
+/// Several variants, separated by commas, expand to a query filter requiring all of them instead,
+/// which is convenient for entities carrying several enums. As in the runtime `VariantFilter`,
+/// variants can also be combined with `!`, `&`, `|` and parentheses, `!` binding tighter than `&`,
+/// which binds tighter than `|`; the comma binds loosest:
+///
+/// ```ignore
+/// // (With<Marker<Movement::Walking>>, With<Marker<Faction::Ally>>)
+/// type Walking = Enum!(Movement::Walking, Faction::Ally);
+/// // (Or<(With<..>, With<..>)>, Without<..>)
+/// type Moving = Enum!(Movement::Walking | Movement::Running, !Combat::Stunned);
+/// // Or<((With<..>, With<..>), With<..>)>
+/// type Guarding = Enum!(Movement::Idle & Combat::Blocking | Faction::Guard);
+/// ```
+///
+/// Bevy has no negation of arbitrary filters, so `!(..)` is pushed down to the variants:
+/// `!(Foo::A, Bar::B)` becomes `Or<(Without<..>, Without<..>)>`.
+///
+/// Only a single bare variant is the marker itself: a parenthesized one, or one followed by a comma,
+/// is a filter like any other input.
+///
+/// ```ignore
+/// // With<Marker<Movement::Walking>>
+/// type Walking = Enum!((Movement::Walking));
+/// type Walking = Enum!(Movement::Walking,);
 /// ```
 ///
+/// If `bevy_ecs_enum_filter` is only reachable through a re-export, the marker is looked up by name
+/// instead, in the `*_filters` module next to the enum, which must then be in scope.
+///
 /// [`EnumComponent`]: derive@EnumComponent
 #[allow(non_snake_case)]
 #[proc_macro]
 pub fn Enum(item: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(item as Path);
+    let input = parse_macro_input!(item as EnumInput);
+    let bevy_ecs_enum_filter = get_crate("bevy_ecs_enum_filter");
+
+    let marker = |path: &Path| enum_marker(path, bevy_ecs_enum_filter.as_ref());
+    let expanded = match input.variant() {
+        Some(path) => marker(path),
+        None => {
+            let bevy_ecs = match &bevy_ecs_enum_filter {
+                Some(bevy_ecs_enum_filter) => get_bevy_ecs(bevy_ecs_enum_filter),
+                None => get_crate("bevy_ecs")
+                    .or_else(|| get_crate("bevy").map(|bevy| quote!(#bevy::ecs)))
+                    .unwrap_or_else(|| quote!(::bevy_ecs)),
+            };
+            input.filter().expand(&bevy_ecs, false, &marker)
+        }
+    };
 
-    let path_len = input.segments.len();
+    TokenStream::from(expanded.unwrap_or_else(syn::Error::into_compile_error))
+}
+
+/// The marker of the variant at `path`, such as `Foo::Bar`.
+fn enum_marker(
+    path: &Path, bevy_ecs_enum_filter: Option<&proc_macro2::TokenStream>,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let path_len = path.segments.len();
 
     if path_len < 2 {
-        return syn::Error::new(input.span(), "expected a valid enum expression (i.e. `Foo::Bar`)")
-            .into_compile_error()
-            .into();
+        return Err(syn::Error::new(path.span(), "expected a valid enum expression (i.e. `Foo::Bar`)"));
     }
 
-    let ident = input.segments[path_len - 2].ident.clone();
-    let variant = input.segments[path_len - 1].ident.clone();
+    let variant = path.segments[path_len - 1].ident.clone();
+    let mut enum_path = path.clone();
+    enum_path.segments.pop();
+    enum_path.segments.pop_punct();
+
+    if let Some(bevy_ecs_enum_filter) = bevy_ecs_enum_filter {
+        let key = variant_key(&variant.to_string());
+        return Ok(quote! {
+            <#enum_path as #bevy_ecs_enum_filter::VariantMarker<#key>>::Marker
+        });
+    }
+
+    let ident = path.segments[path_len - 2].ident.clone();
     let path_prefix =
-        Punctuated::<PathSegment, Token![::]>::from_iter(input.segments.iter().take(path_len - 2).cloned());
+        Punctuated::<PathSegment, Token![::]>::from_iter(path.segments.iter().take(path_len - 2).cloned());
 
     let mod_ident = get_mod_ident(&ident);
 
     let mod_path = if path_prefix.is_empty() { quote!(#mod_ident) } else { quote!(#path_prefix::#mod_ident) };
 
-    TokenStream::from(quote! {
+    Ok(quote! {
         #mod_path::#variant
     })
 }
 
-fn get_mod_ident(enum_ident: &Ident) -> Ident {
-    format_ident!("{}_filters", enum_ident.to_string().to_case(Case::Snake))
+/// The const parameter of `VariantMarker` for the variant `name`: its 64-bit FNV-1a hash,
+/// computed the same way by the derive and by `Enum!`.
+fn variant_key(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
 }
 
-fn get_crate(name: &str) -> proc_macro2::TokenStream {
-    let found_crate = crate_name(name).unwrap_or_else(|_e| panic!("`{}` is present in `Cargo.toml`", name));
+/// The structs borrowing the fields of each variant, their types in `EnumMarker` and the arms matching them.
+struct VariantViews {
+    structs: Vec<proc_macro2::TokenStream>,
+    types: Vec<proc_macro2::TokenStream>,
+    arms: Vec<proc_macro2::TokenStream>,
+}
 
-    match found_crate {
-        FoundCrate::Itself => quote!(crate),
-        FoundCrate::Name(name) => {
-            let ident = Ident::new(&name, proc_macro2::Span::call_site());
-            quote!( #ident )
+/// Builds `FooBRef<'a> { v: &'a f64 }` for `Foo::B { v: f64 }`, or `FooBMut<'a> { v: &'a mut f64 }`
+/// if `mutable`, to be declared in the `foo_filters` module.
+fn variant_views(ident: &Ident, vis: &Visibility, data: &DataEnum, mutable: bool) -> VariantViews {
+    let mod_ident = get_mod_ident(ident);
+    // declared in the `*_filters` module, as visible as the markers
+    let vis = get_marker_vis(vis);
+    let (suffix, query, reference, derive) = match mutable {
+        true => ("Mut", "VariantMut", quote!(&'a mut), None),
+        false => ("Ref", "VariantRef", quote!(&'a), Some(quote!(#[derive(Clone, Copy)]))),
+    };
+    let mut views = VariantViews { structs: vec![], types: vec![], arms: vec![] };
+    let variants = data.variants.iter().map(|variant| &variant.ident).collect::<Vec<_>>();
+
+    for variant in &data.variants {
+        let head = &variant.ident;
+        let view = format_ident!("{}{}{}", ident, head, suffix);
+        let doc = format!(
+            "The fields of [`{ident}::{head}`][super::{ident}::{head}], borrowed, \
+             as yielded by `{query}<Enum!({ident}::{head})>`"
+        );
+
+        let fields = variant.fields.iter().map(|field| {
+            let (name, mut ty) = (&field.ident, field.ty.clone());
+            InFiltersModule { ident, variants: &variants }.visit_type_mut(&mut ty);
+            let colon = name.as_ref().map(|_| quote!(:));
+            quote!(#vis #name #colon #reference #ty)
+        });
+        let body = match variant.fields {
+            Fields::Named(_) => quote!(<'a> { #(#fields),* }),
+            Fields::Unnamed(_) => quote!(<'a>(#(#fields),*);),
+            Fields::Unit => quote!(;),
+        };
+        views.structs.push(quote! {
+            #[doc = #doc]
+            #derive
+            #[allow(dead_code)]
+            #vis struct #view #body
+        });
+
+        views.types.push(match variant.fields {
+            Fields::Unit => quote!(#mod_ident::#view),
+            _ => quote!(#mod_ident::#view<'a>),
+        });
+        let view = quote!(#mod_ident::#view);
+
+        views.arms.push(match &variant.fields {
+            Fields::Named(fields) => {
+                let names = fields.named.iter().map(|field| &field.ident).collect::<Vec<_>>();
+                quote!(#ident::#head { #(#names),* } => Some(#view { #(#names),* }))
+            }
+            Fields::Unnamed(fields) => {
+                let names = (0..fields.unnamed.len()).map(|i| format_ident!("field_{}", i)).collect::<Vec<_>>();
+                quote!(#ident::#head(#(#names),*) => Some(#view(#(#names),*)))
+            }
+            Fields::Unit => quote!(#ident::#head => Some(#view)),
+        });
+    }
+
+    views
+}
+
+/// Rewrites a field type of the enum `ident` for the `*_filters` module, one level deeper, where the views declare
+/// it again: `self::` and `super::` paths go one level up, `Self` names the enum rather than the view, and a
+/// name shadowed there by the marker of a variant, as `Entity` in `Target::Entity(Entity)`, is looked up above.
+struct InFiltersModule<'a> {
+    ident: &'a Ident,
+    variants: &'a [&'a Ident],
+}
+
+impl VisitMut for InFiltersModule<'_> {
+    fn visit_path_mut(&mut self, path: &mut Path) {
+        if path.leading_colon.is_none() {
+            if let Some(first) = path.segments.first_mut() {
+                if first.ident == "Self" {
+                    first.ident = self.ident.clone();
+                } else if first.ident == "self" {
+                    first.ident = Ident::new("super", first.ident.span());
+                } else if first.ident == "super" || self.variants.contains(&&first.ident) {
+                    path.segments.insert(0, parse_quote!(super));
+                }
+            }
         }
+        visit_mut::visit_path_mut(self, path);
     }
 }
 
-#[derive(Debug)]
-struct Source {
-    source_type: Path,
-    source_value: Pat,
+/// Builds a match arm for each variant with fields marked `#[enum_component(entities)]`,
+/// which remaps those fields through `MapEntities`.
+fn map_entities_arms(
+    ident: &Ident, data: &DataEnum, bevy_ecs: &proc_macro2::TokenStream,
+) -> syn::Result<Vec<proc_macro2::TokenStream>> {
+    let mut arms = vec![];
+
+    for variant in &data.variants {
+        let head = &variant.ident;
+        let mut bindings = vec![];
+
+        let pattern = match &variant.fields {
+            Fields::Named(fields) => {
+                for field in &fields.named {
+                    if FieldAttrs::parse(&field.attrs)?.entities {
+                        bindings.push(field.ident.clone().unwrap());
+                    }
+                }
+                quote!(#ident::#head { #(#bindings,)* .. })
+            }
+            Fields::Unnamed(fields) => {
+                let mut elems = vec![];
+                for (index, field) in fields.unnamed.iter().enumerate() {
+                    match FieldAttrs::parse(&field.attrs)?.entities {
+                        true => {
+                            let binding = format_ident!("field_{}", index);
+                            elems.push(quote!(#binding));
+                            bindings.push(binding);
+                        }
+                        false => elems.push(quote!(_)),
+                    }
+                }
+                quote!(#ident::#head ( #(#elems),* ))
+            }
+            Fields::Unit => continue,
+        };
+
+        if !bindings.is_empty() {
+            arms.push(quote! {
+                #pattern => {
+                    #(#bevy_ecs::entity::MapEntities::map_entities(#bindings, mapper);)*
+                }
+            });
+        }
+    }
+
+    Ok(arms)
 }
 
-fn parse_attrs(ast: &DeriveInput) -> syn::Result<Vec<Source>> {
-    let result = ast
-        .attrs
+/// Turns each `from -> to` of `transitions(..)` into a pattern over the pair of variant indices.
+fn transition_rules(variants: &[&Ident], transitions: &Transitions) -> syn::Result<Vec<proc_macro2::TokenStream>> {
+    let side = |side: &Option<Ident>| match side {
+        Some(ident) => match variants.iter().position(|variant| *variant == ident) {
+            Some(index) => Ok(quote!(#index)),
+            None => Err(syn::Error::new(ident.span(), format!("unknown variant `{ident}` in transitions"))),
+        },
+        None => Ok(quote!(_)),
+    };
+
+    transitions
+        .rules
         .iter()
-        .filter(|a| a.path().is_ident("enum_component"))
-        .map(|meta| {
-            let mut source = None;
-            let value = meta.parse_nested_meta(|nested| {
-                let source_type = nested.path.clone();
-                let source_value = Pat::parse_multi(nested.value()?)?;
-                source = Some(Source { source_type, source_value });
-
-                Ok(())
-            });
-            match source {
-                Some(value) => Ok(value),
-                None => match value {
-                    Ok(_) => Err(syn::Error::new(ast.span(), "Couldn't parse EnumComponent storage_type")),
-                    Err(e) => Err(e),
-                },
-            }
+        .map(|rule| {
+            let (from, to) = (side(&rule.from)?, side(&rule.to)?);
+            Ok(quote!((#from, #to)))
         })
-        .collect::<syn::Result<Vec<_>>>()?;
+        .collect()
+}
+
+/// The visibility of the markers inside the generated module, matching the visibility of the enum outside of it.
+fn get_marker_vis(vis: &Visibility) -> proc_macro2::TokenStream {
+    match vis {
+        Visibility::Public(_) => quote!(pub),
+        Visibility::Inherited => quote!(pub(super)),
+        Visibility::Restricted(restricted) => {
+            let path = &restricted.path;
+            if path.is_ident("self") {
+                quote!(pub(super))
+            } else if path.segments.first().is_some_and(|segment| segment.ident == "crate") {
+                quote!(#vis)
+            } else {
+                quote!(pub(in super::#path))
+            }
+        }
+    }
+}
+
+fn get_mod_ident(enum_ident: &Ident) -> Ident {
+    format_ident!("{}_filters", enum_ident.to_string().to_case(Case::Snake))
+}
+
+/// The path to the crate `name` from the crate being compiled: `crate` inside it, and `::name` or its
+/// renamed path from anywhere else, including the examples, integration tests and doctests of `name`.
+fn get_crate(name: &str) -> Option<proc_macro2::TokenStream> {
+    match crate_name(name).ok()? {
+        // examples, integration tests and doctests of the crate are found as `Itself` too,
+        // but can only reach it by name, so the crate being compiled is checked as well
+        FoundCrate::Itself
+            if std::env::var("CARGO_CRATE_NAME").is_ok_and(|crate_name| crate_name == name)
+                && std::env::var_os("UNSTABLE_RUSTDOC_TEST_PATH").is_none() =>
+        {
+            Some(quote!(crate))
+        }
+        FoundCrate::Itself => {
+            let ident = Ident::new(name, proc_macro2::Span::call_site());
+            Some(quote!(::#ident))
+        }
+        FoundCrate::Name(name) => {
+            let ident = Ident::new(&name, proc_macro2::Span::call_site());
+            Some(quote!( #ident ))
+        }
+    }
+}
 
-    Ok(result)
+/// Finds the path to `bevy_ecs` from the user's dependencies, preferring `bevy_ecs` over `bevy`
+/// and falling back to the re-export of `bevy_ecs_enum_filter` when neither is present.
+fn get_bevy_ecs(bevy_ecs_enum_filter: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    if let Some(bevy_ecs) = get_crate("bevy_ecs") {
+        return bevy_ecs;
+    }
+
+    match get_crate("bevy") {
+        Some(bevy) => quote!(#bevy::ecs),
+        None => quote!(#bevy_ecs_enum_filter::__private::bevy_ecs),
+    }
 }
@@ -0,0 +1,102 @@
+#[cfg(feature = "bevy")]
+use bevy::ecs::{
+    entity::Entity,
+    error::{DefaultErrorHandler, ErrorContext},
+    world::World,
+};
+#[cfg(not(feature = "bevy"))]
+use bevy_ecs::{
+    entity::Entity,
+    error::{DefaultErrorHandler, ErrorContext},
+    world::World,
+};
+use std::fmt;
+
+/// An error raised while the hooks generated by [`EnumComponent`](derive@crate::EnumComponent), or those of
+/// an [`EnumSet`](crate::EnumSet), maintain the markers of an entity, or by a command such as
+/// [`modify_variant`](crate::modify_variant).
+///
+/// These errors never abort the hook: they are passed to the world's [`DefaultErrorHandler`],
+/// which panics unless another handler has been configured.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnumFilterError {
+    /// The name of the enum, as in [`EnumComponent::NAME`](crate::EnumComponent::NAME).
+    pub enum_name: &'static str,
+    /// The entity whose markers could not be maintained.
+    pub entity: Entity,
+    /// Where the error was raised.
+    pub origin: EnumFilterErrorOrigin,
+    /// What went wrong.
+    pub kind: EnumFilterErrorKind,
+}
+
+/// Where an [`EnumFilterError`] was raised.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnumFilterErrorOrigin {
+    /// A hook generated by the derive, or a command it queued, named after the hook, such as `on_insert`.
+    Hook(&'static str),
+    /// A command of this crate, such as `modify_variant`.
+    Command(&'static str),
+    /// The transitions declared with `#[enum_component(transitions(..))]`.
+    Transitions,
+}
+
+/// The reason of an [`EnumFilterError`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EnumFilterErrorKind {
+    /// The hook ran, but the enum component was not found on the entity.
+    MissingComponent,
+    /// The entity was despawned before the commands queued by the hook were applied.
+    EntityDespawned,
+    /// The entity went from one variant to another along a transition that is not declared in
+    /// `#[enum_component(transitions(..))]`.
+    IllegalTransition {
+        /// The variant that was left.
+        from: &'static str,
+        /// The variant that was entered.
+        to: &'static str,
+    },
+    /// The entity was not in the variant a command expected.
+    UnexpectedVariant {
+        /// The variant the command expected.
+        expected: &'static str,
+        /// The variant the entity was in.
+        found: &'static str,
+    },
+}
+
+impl fmt::Display for EnumFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let EnumFilterError { enum_name, entity, origin, kind } = self;
+        match origin {
+            EnumFilterErrorOrigin::Hook(hook) => {
+                write!(f, "`{hook}` hook of `{enum_name}` failed for entity {entity}: ")?
+            }
+            EnumFilterErrorOrigin::Command(command) => {
+                write!(f, "`{command}` command of `{enum_name}` failed for entity {entity}: ")?
+            }
+            EnumFilterErrorOrigin::Transitions => {
+                write!(f, "entity {entity} broke the transitions of `{enum_name}`: ")?
+            }
+        }
+        match kind {
+            EnumFilterErrorKind::MissingComponent => write!(f, "the component is missing"),
+            EnumFilterErrorKind::EntityDespawned => write!(f, "the entity no longer exists"),
+            EnumFilterErrorKind::IllegalTransition { from, to } => {
+                write!(f, "illegal transition from `{from}` to `{to}`")
+            }
+            EnumFilterErrorKind::UnexpectedVariant { expected, found } => {
+                write!(f, "expected the variant `{expected}`, found `{found}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EnumFilterError {}
+
+/// Passes `error` to the world's [`DefaultErrorHandler`], as a failed command named after the enum.
+pub fn handle_error(world: &World, error: EnumFilterError) {
+    let handler = world.get_resource::<DefaultErrorHandler>().copied().unwrap_or_default();
+    let name = error.enum_name;
+    (handler.0)(error.into(), ErrorContext::Command { name: name.into() });
+}
@@ -0,0 +1,139 @@
+use crate::EnumComponent;
+#[cfg(feature = "bevy")]
+use bevy::ecs::{
+    entity::Entity,
+    message::{Message, MessageReader, Messages},
+    system::SystemParam,
+    world::DeferredWorld,
+};
+#[cfg(not(feature = "bevy"))]
+use bevy_ecs::{
+    entity::Entity,
+    message::{Message, MessageReader, Messages},
+    system::SystemParam,
+    world::DeferredWorld,
+};
+use std::marker::PhantomData;
+
+/// Written by the generated hooks whenever an entity leaves a variant of `E`, and read through [`ExitedVariants`].
+///
+/// The messages are opt-in: the hooks only write them once they are registered, with
+/// `app.add_message::<VariantExited<E>>()` or `MessageRegistry::register_message::<VariantExited<E>>(world)`.
+#[derive(Message, Debug)]
+pub struct VariantExited<E: EnumComponent> {
+    /// The entity that left the variant.
+    pub entity: Entity,
+    /// The index of the variant that was left, in [`EnumComponent::VARIANTS`].
+    pub exited: usize,
+    /// The index of the variant that was entered instead, or `None` if `E` was removed or the entity despawned.
+    pub entered: Option<usize>,
+    marker: PhantomData<fn() -> E>,
+}
+
+impl<E: EnumComponent> VariantExited<E> {
+    /// Records that `entity` left the variant at `exited` for the one at `entered`, if the messages are registered.
+    pub(crate) fn write(world: &mut DeferredWorld, entity: Entity, exited: usize, entered: Option<usize>) {
+        if let Some(mut messages) = world.get_resource_mut::<Messages<Self>>() {
+            messages.write(Self { entity, exited, entered, marker: PhantomData });
+        }
+    }
+}
+
+/// A [`SystemParam`] yielding `(entity, exited variant, entered variant)` for every variant of `E`
+/// left since the system last ran, with the variants as indices in [`EnumComponent::VARIANTS`].
+///
+/// Unlike `RemovedComponents<E>`, it also reports entities that went from one variant to another.
+/// It requires the [`VariantExited<E>`] messages to be registered.
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use bevy_ecs_enum_filter::{prelude::*, ExitedVariants};
+/// #[derive(Debug, EnumComponent)]
+/// enum Foo {
+///     A,
+///     B(f64),
+/// }
+///
+/// fn on_exit(mut exited: ExitedVariants<Foo>) {
+///     for (entity, exited, entered) in exited.read() {
+///         println!(
+///             "{entity} left {} for {:?}",
+///             Foo::VARIANTS[exited],
+///             entered.map(|entered| Foo::VARIANTS[entered])
+///         );
+///     }
+/// }
+/// # bevy_ecs::system::assert_is_system(on_exit);
+/// ```
+#[derive(SystemParam)]
+pub struct ExitedVariants<'w, 's, E: EnumComponent> {
+    reader: MessageReader<'w, 's, VariantExited<E>>,
+}
+
+impl<E: EnumComponent> ExitedVariants<'_, '_, E> {
+    /// Iterates over the variants left since the system last ran, oldest first.
+    pub fn read(&mut self) -> impl ExactSizeIterator<Item = (Entity, usize, Option<usize>)> + '_ {
+        self.reader.read().map(|exited| (exited.entity, exited.exited, exited.entered))
+    }
+
+    /// Returns the number of variants left since the system last ran.
+    pub fn len(&self) -> usize {
+        self.reader.len()
+    }
+
+    /// Returns `true` if no variant was left since the system last ran.
+    pub fn is_empty(&self) -> bool {
+        self.reader.is_empty()
+    }
+
+    /// Skips every variant left since the system last ran.
+    pub fn clear(&mut self) {
+        self.reader.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    #[cfg(feature = "bevy")]
+    use bevy::ecs::{message::MessageRegistry, resource::Resource, system::ResMut, world::World};
+    #[cfg(not(feature = "bevy"))]
+    use bevy_ecs::{message::MessageRegistry, resource::Resource, system::ResMut, world::World};
+
+    #[allow(unused)]
+    #[derive(Debug, EnumComponent)]
+    enum Foo {
+        A,
+        B(i32),
+        C,
+    }
+
+    #[derive(Resource, Default)]
+    struct Exits(Vec<(Entity, usize, Option<usize>)>);
+
+    #[test]
+    fn test_exited_variants() {
+        let mut world = World::new();
+        MessageRegistry::register_message::<VariantExited<Foo>>(&mut world);
+        world.init_resource::<Exits>();
+        let system = world.register_system(|mut exited: ExitedVariants<Foo>, mut exits: ResMut<Exits>| {
+            exits.0.extend(exited.read());
+        });
+
+        let entity = world.spawn(Foo::A).id();
+        world.entity_mut(entity).insert(Foo::B(1));
+        world.entity_mut(entity).insert(Foo::B(2));
+        world.entity_mut(entity).insert(Foo::C);
+        world.run_system(system).unwrap();
+
+        assert_eq!(world.resource::<Exits>().0, [(entity, 0, Some(1)), (entity, 1, Some(2))]);
+
+        world.entity_mut(entity).remove::<Foo>();
+        let other = world.spawn(Foo::A).id();
+        world.entity_mut(other).despawn();
+        world.run_system(system).unwrap();
+
+        assert_eq!(world.resource::<Exits>().0[2..], [(entity, 2, None), (other, 0, None)]);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::EnumComponent;
+#[cfg(feature = "bevy")]
+use bevy::ecs::{change_detection::Tick, component::Component, entity::Entity, world::DeferredWorld};
+#[cfg(not(feature = "bevy"))]
+use bevy_ecs::{change_detection::Tick, component::Component, entity::Entity, world::DeferredWorld};
+use std::{collections::VecDeque, marker::PhantomData};
+
+/// The variants of `E` an entity has left, most recent last, kept up to date by the generated hooks.
+///
+/// The history is opt-in: insert it next to the enum on the entities that need it. Each entry is
+/// the index of the variant that was left, in [`EnumComponent::VARIANTS`], and the tick at which
+/// it was left. Inserting the same variant again is not a transition and is not recorded.
+/// Once [`capacity`](VariantHistory::capacity) entries are recorded, the oldest one is dropped.
+///
+/// If `E` is [`Clone`], the value the entity had when it last left a variant is kept too, as
+/// [`previous_value`](VariantHistory::previous_value).
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use bevy_ecs_enum_filter::{prelude::*, VariantHistory};
+/// #[derive(Debug, EnumComponent)]
+/// enum Foo {
+///     A,
+///     B(f64),
+/// }
+///
+/// let mut world = World::new();
+/// let entity = world.spawn((Foo::A, VariantHistory::<Foo>::new(4))).id();
+/// world.entity_mut(entity).insert(Foo::B(1.0));
+///
+/// let history = world.get::<VariantHistory<Foo>>(entity).unwrap();
+/// assert_eq!(history.previous_name(), Some("A"));
+/// ```
+#[derive(Component, Debug)]
+pub struct VariantHistory<E: EnumComponent> {
+    entries: VecDeque<(usize, Tick)>,
+    capacity: usize,
+    /// The value the entity had when it last left a variant.
+    previous_value: Option<E>,
+    /// The value last replaced, which becomes `previous_value` if the replacement left its variant.
+    replaced: Option<E>,
+}
+
+impl<E: EnumComponent> VariantHistory<E> {
+    /// The capacity of [`VariantHistory::default`].
+    pub const DEFAULT_CAPACITY: usize = 8;
+
+    /// Creates an empty history, which keeps at most `capacity` entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "the capacity of a VariantHistory must not be zero");
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            previous_value: None,
+            replaced: None,
+        }
+    }
+
+    /// Returns the index of the variant the entity was in before its current one, if any.
+    pub fn previous(&self) -> Option<usize> {
+        self.entries.back().map(|&(variant, _)| variant)
+    }
+
+    /// Returns the name of the variant the entity was in before its current one, if any.
+    pub fn previous_name(&self) -> Option<&'static str> {
+        self.previous().map(|variant| E::VARIANTS[variant])
+    }
+
+    /// Returns the value the entity had before entering its current variant, if any.
+    ///
+    /// Only kept for an `E` that is [`Clone`]: otherwise this is always `None`.
+    pub fn previous_value(&self) -> Option<&E> {
+        self.previous_value.as_ref()
+    }
+
+    /// Iterates over the entries, from the oldest to the most recent.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (usize, Tick)> + ExactSizeIterator + '_ {
+        self.entries.iter().copied()
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no variant has been left yet.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the maximum number of entries.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Removes every entry, and the previous value.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.previous_value = None;
+        self.replaced = None;
+    }
+
+    /// Records that `entity` left the variant at `variant`, if it has a history.
+    pub(crate) fn record(world: &mut DeferredWorld, entity: Entity, variant: usize) {
+        let tick = world.read_change_tick();
+        if let Some(mut history) = world.get_mut::<Self>(entity) {
+            if history.entries.len() == history.capacity {
+                history.entries.pop_front();
+            }
+            history.entries.push_back((variant, tick));
+            history.previous_value = history.replaced.take();
+        }
+    }
+}
+
+/// Keeps the value of `E` replaced on an entity with a [`VariantHistory`], if `E` is [`Clone`].
+///
+/// Called by `on_replace` as `(&KeepReplaced::<E>::default()).keep(..)`, which resolves to
+/// [`KeepClone::keep`] when `E` is [`Clone`] and to the no-op [`KeepNothing::keep`] otherwise.
+#[doc(hidden)]
+pub struct KeepReplaced<E>(PhantomData<fn() -> E>);
+
+impl<E> Default for KeepReplaced<E> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[doc(hidden)]
+pub trait KeepClone {
+    fn keep(&self, world: &mut DeferredWorld, entity: Entity);
+}
+
+impl<E: EnumComponent + Clone> KeepClone for KeepReplaced<E> {
+    fn keep(&self, world: &mut DeferredWorld, entity: Entity) {
+        // only entities that opted into a history pay for the clone
+        if world.get::<VariantHistory<E>>(entity).is_none() {
+            return;
+        }
+        let value = world.get::<E>(entity).cloned();
+        if let Some(mut history) = world.get_mut::<VariantHistory<E>>(entity) {
+            history.replaced = value;
+        }
+    }
+}
+
+#[doc(hidden)]
+pub trait KeepNothing {
+    fn keep(&self, world: &mut DeferredWorld, entity: Entity) {
+        let _ = (world, entity);
+    }
+}
+
+impl<E> KeepNothing for &KeepReplaced<E> {}
+
+impl<E: EnumComponent> Default for VariantHistory<E> {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    #[cfg(feature = "bevy")]
+    use bevy::ecs::world::World;
+    #[cfg(not(feature = "bevy"))]
+    use bevy_ecs::world::World;
+
+    #[allow(unused)]
+    #[derive(Clone, Debug, PartialEq, EnumComponent)]
+    enum Foo {
+        A,
+        B(i32),
+        C { v: f64 },
+    }
+
+    #[test]
+    fn test_history() {
+        let mut world = World::new();
+        let entity = world.spawn((Foo::A, VariantHistory::<Foo>::new(2))).id();
+        assert!(world.get::<VariantHistory<Foo>>(entity).unwrap().is_empty());
+
+        world.entity_mut(entity).insert(Foo::B(1));
+        world.entity_mut(entity).insert(Foo::B(2));
+
+        let history = world.get::<VariantHistory<Foo>>(entity).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.previous(), Some(0));
+        assert_eq!(history.previous_value(), Some(&Foo::A));
+
+        let before = world.change_tick();
+        world.increment_change_tick();
+        world.entity_mut(entity).insert(Foo::C { v: 1.0 });
+        world.entity_mut(entity).insert(Foo::A);
+
+        let history = world.get::<VariantHistory<Foo>>(entity).unwrap();
+        assert_eq!(history.iter().map(|(variant, _)| variant).collect::<Vec<_>>(), [1, 2]);
+        assert!(history.iter().all(|(_, tick)| tick.is_newer_than(before, world.read_change_tick())));
+        assert_eq!(history.previous_name(), Some("C"));
+        assert_eq!(history.previous_value(), Some(&Foo::C { v: 1.0 }));
+
+        world.entity_mut(entity).remove::<Foo>();
+
+        let history = world.get::<VariantHistory<Foo>>(entity).unwrap();
+        assert_eq!(history.previous_name(), Some("A"));
+        assert_eq!(history.previous_value(), Some(&Foo::A));
+
+        // the value replaced before clearing cannot come back with the next entry
+        world.entity_mut(entity).insert(Foo::B(3));
+        world.entity_mut(entity).insert(Foo::B(4));
+        let mut history = world.get_mut::<VariantHistory<Foo>>(entity).unwrap();
+        assert_eq!(history.replaced, Some(Foo::B(3)));
+        history.clear();
+        assert!(history.is_empty() && history.previous_value().is_none() && history.replaced.is_none());
+    }
+
+    thread_local! {
+        static CLONES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    #[allow(unused)]
+    #[derive(Debug, EnumComponent)]
+    enum Counted {
+        A,
+        B,
+    }
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            CLONES.set(CLONES.get() + 1);
+            match self {
+                Counted::A => Counted::A,
+                Counted::B => Counted::B,
+            }
+        }
+    }
+
+    #[test]
+    fn test_history_clones_only_with_history() {
+        let mut world = World::new();
+        let entity = world.spawn(Counted::A).id();
+        world.entity_mut(entity).insert(Counted::B);
+        assert_eq!(CLONES.get(), 0);
+
+        world.entity_mut(entity).insert(VariantHistory::<Counted>::default());
+        world.entity_mut(entity).insert(Counted::A);
+        assert_eq!(CLONES.get(), 1);
+    }
+
+    // deliberately not Clone: the variants are recorded, but not the values
+    #[allow(unused)]
+    #[derive(Debug, EnumComponent)]
+    enum Unclonable {
+        A,
+        B(Vec<u8>),
+    }
+
+    #[test]
+    fn test_history_without_clone() {
+        let mut world = World::new();
+        let entity = world.spawn((Unclonable::B(vec![1]), VariantHistory::<Unclonable>::default())).id();
+        world.entity_mut(entity).insert(Unclonable::A);
+
+        let history = world.get::<VariantHistory<Unclonable>>(entity).unwrap();
+        assert_eq!(history.previous_name(), Some("B"));
+        assert!(history.previous_value().is_none());
+    }
+}
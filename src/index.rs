@@ -0,0 +1,137 @@
+use crate::{EnumComponent, EnumMarker};
+#[cfg(feature = "bevy")]
+use bevy::ecs::{
+    entity::{Entity, EntityHashSet},
+    resource::Resource,
+    world::{FromWorld, World},
+};
+#[cfg(not(feature = "bevy"))]
+use bevy_ecs::{
+    entity::{Entity, EntityHashSet},
+    resource::Resource,
+    world::{FromWorld, World},
+};
+use std::marker::PhantomData;
+
+/// The entities currently in each variant of `E`, kept up to date by the generated hooks.
+///
+/// The index is opt-in: the hooks only maintain it once it has been added to the world, with
+/// `world.init_resource::<VariantIndex<E>>()`, which also indexes the entities that already exist.
+///
+/// [`World::clear_entities`] runs no hooks, so the index still holds the cleared entities afterwards.
+/// Build it again from the world to start over, as in the example below.
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use bevy_ecs_enum_filter::{prelude::*, VariantIndex};
+/// #[derive(Debug, EnumComponent)]
+/// enum Foo {
+///     A,
+///     B(f64),
+/// }
+///
+/// let mut world = World::new();
+/// world.init_resource::<VariantIndex<Foo>>();
+/// let entity = world.spawn(Foo::B(1.0)).id();
+///
+/// let index = world.resource::<VariantIndex<Foo>>();
+/// assert!(index.entities::<Enum!(Foo::B)>().contains(&entity));
+/// assert!(index.entities::<Enum!(Foo::A)>().is_empty());
+///
+/// world.clear_entities();
+/// let index = VariantIndex::<Foo>::from_world(&mut world);
+/// world.insert_resource(index);
+/// assert!(world.resource::<VariantIndex<Foo>>().entities::<Enum!(Foo::B)>().is_empty());
+/// ```
+#[derive(Resource, Debug)]
+pub struct VariantIndex<E: EnumComponent> {
+    variants: Vec<EntityHashSet>,
+    marker: PhantomData<fn() -> E>,
+}
+
+impl<E: EnumComponent> VariantIndex<E> {
+    /// Returns the entities in the variant of the marker `M`.
+    pub fn entities<M: EnumMarker<Enum = E>>(&self) -> &EntityHashSet {
+        &self.variants[M::INDEX]
+    }
+
+    /// Returns the entities in the variant at `index` in [`EnumComponent::VARIANTS`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn entities_at(&self, index: usize) -> &EntityHashSet {
+        &self.variants[index]
+    }
+
+    /// Returns the index of the variant `entity` is in, if it is indexed.
+    pub fn variant_of(&self, entity: Entity) -> Option<usize> {
+        self.variants.iter().position(|entities| entities.contains(&entity))
+    }
+
+    pub(crate) fn insert(&mut self, entity: Entity, index: usize) {
+        self.variants[index].insert(entity);
+    }
+
+    pub(crate) fn remove(&mut self, entity: Entity, index: usize) {
+        self.variants[index].remove(&entity);
+    }
+}
+
+impl<E: EnumComponent> FromWorld for VariantIndex<E> {
+    fn from_world(world: &mut World) -> Self {
+        let variants = vec![EntityHashSet::default(); E::VARIANTS.len()];
+        let mut index = Self { variants, marker: PhantomData };
+        for (entity, value) in world.query::<(Entity, &E)>().iter(world) {
+            index.insert(entity, value.variant_index());
+        }
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[allow(unused)]
+    #[derive(Debug, EnumComponent)]
+    enum Foo {
+        A,
+        B(i32),
+        C { v: f64 },
+    }
+
+    #[test]
+    fn test_variant_index() {
+        let mut world = World::new();
+        let existing = world.spawn(Foo::B(1)).id();
+        world.init_resource::<VariantIndex<Foo>>();
+
+        let entity = world.spawn(Foo::A).id();
+        let index = world.resource::<VariantIndex<Foo>>();
+        assert_eq!(index.entities::<Enum!(Foo::A)>().len(), 1);
+        assert!(index.entities::<Enum!(Foo::B)>().contains(&existing));
+        assert_eq!(index.variant_of(entity), Some(0));
+
+        world.entity_mut(entity).insert(Foo::C { v: 1.0 });
+        let index = world.resource::<VariantIndex<Foo>>();
+        assert!(index.entities::<Enum!(Foo::A)>().is_empty());
+        assert!(index.entities_at(2).contains(&entity));
+
+        world.entity_mut(entity).remove::<Foo>();
+        world.entity_mut(existing).despawn();
+        let index = world.resource::<VariantIndex<Foo>>();
+        assert_eq!(index.variant_of(entity), None);
+        assert!((0..Foo::VARIANTS.len()).all(|variant| index.entities_at(variant).is_empty()));
+
+        // clearing runs no hooks: the index is stale until it is built again
+        let entity = world.spawn(Foo::A).id();
+        world.clear_entities();
+        assert_eq!(world.resource::<VariantIndex<Foo>>().variant_of(entity), Some(0));
+
+        let index = VariantIndex::<Foo>::from_world(&mut world);
+        world.insert_resource(index);
+        assert_eq!(world.resource::<VariantIndex<Foo>>().variant_of(entity), None);
+    }
+}
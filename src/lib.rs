@@ -1,29 +1,384 @@
 #![doc = include_str!("../README.md")]
 
-pub use bevy_ecs_enum_filter_derive::{Enum, EnumComponent};
+mod error;
+mod exited;
+mod history;
+mod index;
+mod query;
+mod resource;
+mod set;
+mod since;
+#[cfg(feature = "state")]
+mod state;
+mod variant;
+
+pub use bevy_ecs_enum_filter_derive::{Enum, EnumComponent, EnumResource, EnumSetComponent};
+pub use error::{EnumFilterError, EnumFilterErrorKind, EnumFilterErrorOrigin};
+pub use exited::{ExitedVariants, VariantExited};
+pub use history::VariantHistory;
+pub use index::VariantIndex;
+pub use query::{EnumFilterRegistry, EnumFilterWorldExt, VariantFilter, VariantFilterError};
+pub use resource::{EnumResource, ResourceMarker, entered_variant, exited_variant, in_variant};
+pub use set::{EnumSet, EnumSetComponent};
+#[cfg(feature = "bevy")]
+pub use since::InVariantFor;
+pub use since::{JustEntered, PayloadChanged, VariantChanged, VariantSince};
+#[cfg(feature = "state")]
+pub use state::{EnumStatePlugin, StateSource, StateTarget};
+pub use variant::{VariantMut, VariantRef, modify_variant};
 
 pub mod prelude {
-    pub use crate::EnumComponent;
+    pub use crate::{EnumComponent, EnumFilterWorldExt, EnumResource, EnumSetComponent};
     pub use bevy_ecs_enum_filter_derive::Enum;
 }
 
-/// A trait used to denote an enum as "filterable".
-#[cfg(not(feature = "bevy"))]
-pub trait EnumComponent: Clone + bevy_ecs::component::Component {}
+/// Re-exports and helpers used by the code generated with [`EnumComponent`](derive@EnumComponent). Not public API.
+#[doc(hidden)]
+pub mod __private {
+    use crate::{
+        EnumComponent, EnumFilterError, EnumFilterErrorKind, EnumFilterErrorOrigin, VariantExited, VariantHistory,
+        VariantIndex, VariantSince,
+    };
+    pub use crate::{
+        error::handle_error,
+        history::{KeepClone, KeepNothing, KeepReplaced},
+    };
+    pub use bevy_ecs;
+    use bevy_ecs::{
+        bundle::Bundle,
+        component::{Component, ComponentCloneBehavior},
+        entity::{ComponentCloneCtx, Entity, SourceComponent},
+        world::{DeferredWorld, EntityWorldMut, World},
+    };
+
+    /// Bookkeeping of the generated hooks for one entity, required by every enum component.
+    #[derive(Component)]
+    #[component(clone_behavior = Custom(clone_state::<E>))]
+    pub struct EnumState<E: EnumComponent> {
+        /// Set by `on_despawn`, which runs before `on_replace` and `on_remove` when the entity is despawned,
+        /// so that they skip the markers that are about to disappear with it.
+        pub despawning: bool,
+        /// The variant left in `on_replace`, until `on_insert` enters the next one or `on_remove` runs.
+        previous: Option<usize>,
+        /// The replaced value, kept by `on_replace` with `strict_transitions` to revert an illegal transition.
+        stash: Option<E>,
+        /// Set while a reverted value is inserted back, which is not checked against the transitions,
+        /// and which the user's hooks skip.
+        reverting: bool,
+        /// Set by `modify_variant` while it replaces the value within the same variant,
+        /// so that `on_replace` and `on_insert` leave the markers alone.
+        pub modifying: bool,
+        /// One bit per component required by a variant, set when the generated hook inserted it,
+        /// so that leaving the variant only removes what entering it added.
+        required: u64,
+    }
+
+    impl<E: EnumComponent> Default for EnumState<E> {
+        fn default() -> Self {
+            Self {
+                despawning: false,
+                previous: None,
+                stash: None,
+                reverting: false,
+                modifying: false,
+                required: 0,
+            }
+        }
+    }
+
+    /// Clones which required components the hooks inserted along with them, unless `E` itself is not cloned.
+    fn clone_state<E: EnumComponent>(source: &SourceComponent, ctx: &mut ComponentCloneCtx) {
+        if matches!(E::clone_behavior(), ComponentCloneBehavior::Ignore) {
+            return;
+        }
+        if let Some(state) = source.read::<EnumState<E>>() {
+            let required = state.required;
+            ctx.write_target_component(EnumState::<E> { required, ..Default::default() });
+        }
+    }
+
+    /// Called by `on_insert` before `entity` enters the variant at `index`.
+    ///
+    /// Returns `false` when the transition is illegal and reverted, or when `modify_variant` stays in the
+    /// same variant, in which case no markers are inserted.
+    pub fn enter<E: EnumComponent>(world: &mut DeferredWorld, entity: Entity, index: usize) -> bool {
+        if let Some(mut state) = world.get_mut::<EnumState<E>>(entity)
+            && std::mem::take(&mut state.modifying)
+        {
+            return false;
+        }
+        let (previous, stash, reverting) = match world.get_mut::<EnumState<E>>(entity) {
+            Some(mut state) => (state.previous.take(), state.stash.take(), std::mem::take(&mut state.reverting)),
+            None => (None, None, false),
+        };
+        // a reverted value goes back to where it was, as if it had never left: its marker, bundle, required
+        // components and `VariantSince` were left alone, only the index has to list it again
+        if reverting {
+            if let Some(mut variant_index) = world.get_resource_mut::<VariantIndex<E>>() {
+                variant_index.insert(entity, index);
+            }
+            return false;
+        }
+
+        if let Some(previous) = previous.filter(|&previous| !E::allows_transition(previous, index)) {
+            match stash {
+                Some(value) => {
+                    if let Some(mut state) = world.get_mut::<EnumState<E>>(entity) {
+                        state.reverting = true;
+                    }
+                    world.commands().queue(move |world: &mut World| {
+                        entity_mut::<E>(world, entity, "on_insert")?.insert(value);
+                        Ok::<(), EnumFilterError>(())
+                    });
+                    return false;
+                }
+                None => {
+                    let kind = EnumFilterErrorKind::IllegalTransition {
+                        from: E::VARIANTS[previous],
+                        to: E::VARIANTS[index],
+                    };
+                    handle_error(
+                        world,
+                        EnumFilterError {
+                            enum_name: E::NAME,
+                            entity,
+                            origin: EnumFilterErrorOrigin::Transitions,
+                            kind,
+                        },
+                    );
+                }
+            }
+        }
+
+        if let Some(mut variant_index) = world.get_resource_mut::<VariantIndex<E>>() {
+            variant_index.insert(entity, index);
+        }
+        if let Some(previous) = previous.filter(|&previous| previous != index) {
+            VariantHistory::<E>::record(world, entity, previous);
+            VariantExited::<E>::write(world, entity, previous, Some(index));
+        }
+        if previous != Some(index) {
+            VariantSince::<E>::enter(world, entity);
+        }
+        true
+    }
+
+    /// Called by `on_replace` when `entity` leaves the variant at `index`.
+    pub fn exit<E: EnumComponent>(world: &mut DeferredWorld, entity: Entity, index: usize) {
+        if let Some(mut state) = world.get_mut::<EnumState<E>>(entity) {
+            state.previous = Some(index);
+        }
+        if let Some(mut variant_index) = world.get_resource_mut::<VariantIndex<E>>() {
+            variant_index.remove(entity, index);
+        }
+    }
+
+    /// Implemented by every enum that can use `strict_transitions`, which inserts a clone of the replaced value back.
+    #[diagnostic::on_unimplemented(
+        message = "`#[enum_component(strict_transitions(..))]` requires `{Self}` to implement `Clone`",
+        label = "the replaced value is cloned to be inserted back"
+    )]
+    pub trait StrictTransitions: Clone {}
+
+    impl<E: Clone> StrictTransitions for E {}
+
+    /// Called by `on_replace` with `strict_transitions`, to keep the replaced value until the transition is checked.
+    pub fn stash<E: EnumComponent + StrictTransitions>(world: &mut DeferredWorld, entity: Entity) {
+        let value = world.get::<E>(entity).cloned();
+        if let Some(mut state) = world.get_mut::<EnumState<E>>(entity) {
+            state.stash = value;
+        }
+    }
+
+    /// Whether the value of `entity` is illegal and about to be reverted, which the user's hooks skip,
+    /// and which neither leaves the previous variant nor enters its own.
+    pub fn reverting<E: EnumComponent>(world: &World, entity: Entity) -> bool {
+        world.get::<EnumState<E>>(entity).is_some_and(|state| state.reverting)
+    }
+
+    /// Called by `on_remove`, after `on_replace` has left the variant: no variant is entered next.
+    ///
+    /// The bookkeeping required by every enum component goes too, unless `E` is inserted back in the meantime.
+    pub fn remove<E: EnumComponent>(world: &mut DeferredWorld, entity: Entity) {
+        let (previous, despawning) = world.get_mut::<EnumState<E>>(entity).map_or((None, false), |mut state| {
+            state.stash = None;
+            (state.previous.take(), state.despawning)
+        });
+        if let Some(previous) = previous {
+            VariantHistory::<E>::record(world, entity, previous);
+            VariantExited::<E>::write(world, entity, previous, None);
+        }
+        if !despawning {
+            world.commands().queue(move |world: &mut World| {
+                let mut entity_mut = entity_mut::<E>(world, entity, "on_remove")?;
+                if !entity_mut.contains::<E>() {
+                    entity_mut.remove::<EnumState<E>>();
+                }
+                Ok::<(), EnumFilterError>(())
+            });
+        }
+    }
+
+    /// Reports that the hook `hook` of `E` ran while `E` was missing from `entity`.
+    pub fn missing_component<E: EnumComponent>(world: &World, entity: Entity, hook: &'static str) {
+        let kind = EnumFilterErrorKind::MissingComponent;
+        let origin = EnumFilterErrorOrigin::Hook(hook);
+        handle_error(world, EnumFilterError { enum_name: E::NAME, entity, origin, kind });
+    }
+
+    /// Fetches `entity` for a command queued by the hook `hook` of `E`, which may run after a despawn.
+    pub fn entity_mut<'w, E: EnumComponent>(
+        world: &'w mut World, entity: Entity, hook: &'static str,
+    ) -> Result<EntityWorldMut<'w>, EnumFilterError> {
+        let kind = EnumFilterErrorKind::EntityDespawned;
+        world.get_entity_mut(entity).map_err(|_| EnumFilterError {
+            enum_name: E::NAME,
+            entity,
+            origin: EnumFilterErrorOrigin::Hook(hook),
+            kind,
+        })
+    }
+
+    /// Inserts the component required by a variant unless `entity` already has it, and returns whether it did.
+    pub fn insert_required<C: Component>(entity: &mut EntityWorldMut, value: impl FnOnce() -> C) -> bool {
+        let missing = !entity.contains::<C>();
+        if missing {
+            entity.insert(value());
+        }
+        missing
+    }
+
+    /// Records the bits of the components required by a variant that the insertion command inserted.
+    pub fn own_required<E: EnumComponent>(entity: &mut EntityWorldMut, required: u64) {
+        if let Some(mut state) = entity.get_mut::<EnumState<E>>() {
+            state.required |= required;
+        }
+    }
+
+    /// Takes the bits in `mask` of the components required by the variant at `index` that the hooks inserted,
+    /// unless `entity` is back in that variant by the time the removal command runs.
+    pub fn take_required<E: EnumComponent>(entity: &mut EntityWorldMut, index: usize, mask: u64) -> u64 {
+        if entity.get::<E>().is_some_and(|value| value.variant_index() == index) {
+            return 0;
+        }
+        entity.get_mut::<EnumState<E>>().map_or(0, |mut state| {
+            let required = state.required & mask;
+            state.required &= !mask;
+            required
+        })
+    }
+
+    /// Removes the component required by a variant, of the type built by `_value`.
+    pub fn remove_required<C: Component>(entity: &mut EntityWorldMut, _value: impl FnOnce() -> C) {
+        entity.remove::<C>();
+    }
+
+    /// Removes the components of the bundle built by `_bundle_fn` when leaving its variant.
+    pub fn remove_variant_bundle<E, B: Bundle>(entity: &mut EntityWorldMut, _bundle_fn: fn(&E) -> B) {
+        entity.remove::<B>();
+    }
+}
+
 #[cfg(feature = "bevy")]
-pub trait EnumComponent: Clone + bevy::prelude::Component {}
+use bevy::ecs::{
+    component::{Component, ComponentId},
+    world::World,
+};
+#[cfg(not(feature = "bevy"))]
+use bevy_ecs::{
+    component::{Component, ComponentId},
+    world::World,
+};
+
+/// A trait used to denote an enum as "filterable".
+pub trait EnumComponent: Component {
+    /// The name of the enum, as written in front of a variant in an [`Enum!`] path.
+    const NAME: &'static str;
+    /// The names of the enum's variants, in declaration order.
+    const VARIANTS: &'static [&'static str];
+
+    /// Registers the marker component of every variant and returns their ids, in declaration order.
+    fn register_markers(world: &mut World) -> Vec<ComponentId>;
+
+    /// Returns the index of the active variant in [`VARIANTS`](EnumComponent::VARIANTS).
+    fn variant_index(&self) -> usize;
+
+    /// Whether an entity may go from the variant at `from` to the variant at `to`, both indices in
+    /// [`VARIANTS`](EnumComponent::VARIANTS).
+    ///
+    /// Generated from `#[enum_component(transitions(A -> B, _ -> A))]`, where `_` is any variant.
+    /// Staying in the same variant is always allowed, and so is everything without the attribute.
+    fn allows_transition(from: usize, to: usize) -> bool {
+        let _ = (from, to);
+        true
+    }
+}
+
+/// Implemented by the marker components generated by the [`EnumComponent`](derive@EnumComponent) derive.
+pub trait EnumMarker: Component {
+    /// The enum the marker was generated for.
+    type Enum: EnumComponent;
+    /// The index of the marker's variant in [`EnumComponent::VARIANTS`].
+    const INDEX: usize;
+    /// The fields of the marker's variant borrowed from the enum, such as `foo_filters::FooBRef<'a>`
+    /// for `Foo::B`, generated next to the marker.
+    type Ref<'a>;
+
+    /// Borrows the fields of `value` if it is in the marker's variant.
+    fn as_variant(value: &Self::Enum) -> Option<Self::Ref<'_>>;
+
+    /// Shortens the lifetime of a [`Ref`](EnumMarker::Ref), which the generated structs are covariant in.
+    #[doc(hidden)]
+    fn shrink_ref<'long: 'short, 'short>(fields: Self::Ref<'long>) -> Self::Ref<'short>;
+
+    /// The fields of the marker's variant borrowed mutably from the enum, such as `foo_filters::FooBMut<'a>`
+    /// for `Foo::B`, generated next to the marker.
+    type Mut<'a>;
+
+    /// Borrows the fields of `value` mutably if it is in the marker's variant.
+    fn as_variant_mut(value: &mut Self::Enum) -> Option<Self::Mut<'_>>;
+
+    /// Shortens the lifetime of a [`Mut`](EnumMarker::Mut), which the generated structs are covariant in.
+    #[doc(hidden)]
+    fn shrink_mut<'long: 'short, 'short>(fields: Self::Mut<'long>) -> Self::Mut<'short>;
+}
+
+/// Implemented by the [`EnumComponent`](derive@EnumComponent) derive for every variant of the enum,
+/// where `VARIANT` identifies the variant by a hash of its name.
+///
+/// This is how [`Enum!`] finds the marker of a variant from any path to the enum. Use [`Enum!`]
+/// rather than spelling out `VARIANT`.
+pub trait VariantMarker<const VARIANT: u64> {
+    /// The marker component of the variant.
+    type Marker: Component;
+}
 
 #[cfg(test)]
 mod tests {
-    use super::prelude::*;
+    use super::{
+        __private::EnumState, EnumFilterError, EnumFilterErrorKind, EnumFilterErrorOrigin, JustEntered,
+        VariantSince, prelude::*,
+    };
     #[cfg(feature = "bevy")]
-    use bevy::ecs::component;
+    use bevy::ecs::{
+        component,
+        error::{BevyError, DefaultErrorHandler, ErrorContext},
+        lifecycle::HookContext,
+        query::QueryFilter,
+        world::DeferredWorld,
+    };
     #[cfg(feature = "bevy")]
     use bevy::prelude::*;
     #[cfg(not(feature = "bevy"))]
-    use bevy_ecs::component;
-    #[cfg(not(feature = "bevy"))]
     use bevy_ecs::prelude::*;
+    #[cfg(not(feature = "bevy"))]
+    use bevy_ecs::{
+        component,
+        error::{BevyError, DefaultErrorHandler, ErrorContext},
+        lifecycle::HookContext,
+        query::QueryFilter,
+        world::DeferredWorld,
+    };
 
     #[allow(unused)]
     #[derive(Clone, Debug, Default, EnumComponent)]
@@ -69,6 +424,624 @@ mod tests {
         assert!(world.query_filtered::<Entity, With<Enum!(TestEnum::C)>>().single(&world).is_ok());
     }
 
+    mod reexports {
+        pub use crate as enum_filter;
+    }
+
+    #[allow(unused)]
+    #[derive(Clone, Debug, EnumComponent)]
+    #[enum_component(crate = reexports::enum_filter)]
+    enum TestReexported {
+        A,
+        B(i32),
+    }
+
+    #[allow(unused)]
+    #[derive(Clone, Debug, EnumComponent)]
+    #[enum_component(sparse_set, immutable)]
+    enum TestShorthand {
+        A,
+    }
+
+    #[test]
+    fn test_attrs() {
+        assert_eq!(<TestEnum as Component>::STORAGE_TYPE, component::StorageType::SparseSet);
+        const { assert!(!<<TestEnum as Component>::Mutability as component::ComponentMutability>::MUTABLE) };
+        assert_eq!(<TestShorthand as Component>::STORAGE_TYPE, component::StorageType::SparseSet);
+        const { assert!(!<<TestShorthand as Component>::Mutability as component::ComponentMutability>::MUTABLE) };
+        assert_eq!(<TestReexported as Component>::STORAGE_TYPE, component::StorageType::Table);
+        const { assert!(<<TestReexported as Component>::Mutability as component::ComponentMutability>::MUTABLE) };
+    }
+
+    #[test]
+    fn test_crate_path() {
+        let mut world = World::new();
+        let entity = world.spawn(TestReexported::A).id();
+
+        assert!(world.query_filtered::<Entity, With<Enum!(TestReexported::A)>>().single(&world).is_ok());
+
+        world.entity_mut(entity).insert(TestReexported::B(0));
+
+        assert!(world.query_filtered::<Entity, With<Enum!(TestReexported::A)>>().single(&world).is_err());
+        assert!(world.query_filtered::<Entity, With<Enum!(TestReexported::B)>>().single(&world).is_ok());
+    }
+
+    #[derive(Resource, Default)]
+    struct HookCalls(Vec<&'static str>);
+
+    fn record(name: &'static str) -> impl Fn(DeferredWorld, HookContext) {
+        move |mut world, HookContext { entity, .. }| {
+            // the enum is still readable from every user hook
+            assert!(world.entity(entity).contains::<TestHooked>());
+            world.resource_mut::<HookCalls>().0.push(name);
+        }
+    }
+
+    fn record_insert(mut world: DeferredWorld, context: HookContext) {
+        record("insert")(world.reborrow(), context);
+
+        let entity = context.entity;
+        let in_b = matches!(world.get::<TestHooked>(entity), Some(TestHooked::B));
+        let has_marker = move |entity: EntityRef| match in_b {
+            true => entity.contains::<Enum!(TestHooked::B)>(),
+            false => entity.contains::<Enum!(TestHooked::A)>(),
+        };
+        // the marker is inserted by the command of the generated hook, which runs before the user's commands
+        assert!(!has_marker(world.entity(entity)));
+        world.commands().queue(move |world: &mut World| assert!(has_marker(world.entity(entity))));
+    }
+
+    #[allow(unused)]
+    #[derive(Clone, Debug, EnumComponent)]
+    #[enum_component(on_add = record("add"), on_insert = record_insert, on_replace = record("replace"))]
+    #[enum_component(on_remove = record("remove"), on_despawn = record("despawn"))]
+    enum TestHooked {
+        A,
+        B,
+    }
+
+    #[test]
+    fn test_user_hooks() {
+        let mut world = World::new();
+        world.init_resource::<HookCalls>();
+        let take_calls = |world: &mut World| std::mem::take(&mut world.resource_mut::<HookCalls>().0);
+
+        let entity = world.spawn(TestHooked::A).id();
+        assert_eq!(take_calls(&mut world), ["add", "insert"]);
+        assert!(world.entity(entity).contains::<Enum!(TestHooked::A)>());
+
+        world.entity_mut(entity).insert(TestHooked::B);
+        assert_eq!(take_calls(&mut world), ["replace", "insert"]);
+        assert!(!world.entity(entity).contains::<Enum!(TestHooked::A)>());
+        assert!(world.entity(entity).contains::<Enum!(TestHooked::B)>());
+
+        world.entity_mut(entity).remove::<TestHooked>();
+        assert_eq!(take_calls(&mut world), ["replace", "remove"]);
+        assert!(!world.entity(entity).contains::<Enum!(TestHooked::B)>());
+
+        world.entity_mut(entity).insert(TestHooked::A);
+        world.despawn(entity);
+        assert_eq!(take_calls(&mut world), ["add", "insert", "despawn", "replace", "remove"]);
+    }
+
+    #[derive(Component, Debug, Default, PartialEq)]
+    struct Health(u32);
+
+    #[derive(Component, Debug, Default, PartialEq)]
+    struct Velocity(f32);
+
+    #[derive(Component, Debug, Default, PartialEq)]
+    struct Grounded;
+
+    #[derive(Component, Debug, PartialEq)]
+    enum Gait {
+        Step,
+    }
+
+    #[allow(unused)]
+    #[derive(Clone, Debug, EnumComponent)]
+    #[enum_component(require(Health(100)))]
+    enum TestRequired {
+        #[enum_component(require(Velocity = Velocity(1.0), Grounded, Gait::Step))]
+        Walk,
+        #[enum_component(require(Velocity))]
+        Fall { height: f32 },
+        #[enum_component(require(Health(1)))]
+        Idle(u8, u8),
+    }
+
+    #[test]
+    fn test_required() {
+        let mut world = World::new();
+        let entity = world.spawn(TestRequired::Walk).id();
+
+        assert_eq!(world.get::<Health>(entity), Some(&Health(100)));
+        assert_eq!(world.get::<Velocity>(entity), Some(&Velocity(1.0)));
+        assert!(world.entity(entity).contains::<Grounded>());
+        assert_eq!(world.get::<Gait>(entity), Some(&Gait::Step));
+
+        // staying in the same variant keeps the required components as they are
+        world.entity_mut(entity).insert(Velocity(5.0));
+        world.entity_mut(entity).insert(TestRequired::Walk);
+
+        assert_eq!(world.get::<Velocity>(entity), Some(&Velocity(5.0)));
+        assert!(world.entity(entity).contains::<Grounded>());
+
+        world.entity_mut(entity).insert(TestRequired::Fall { height: 2.0 });
+
+        assert_eq!(world.get::<Velocity>(entity), Some(&Velocity(0.0)));
+        assert!(!world.entity(entity).contains::<Grounded>());
+        assert!(!world.entity(entity).contains::<Gait>());
+
+        // `Health` is required by the enum itself, so neither entering nor leaving `Idle` touches it
+        world.entity_mut(entity).insert(TestRequired::Idle(0, 0));
+
+        assert!(!world.entity(entity).contains::<Velocity>());
+        assert!(world.entity(entity).contains::<Enum!(TestRequired::Idle)>());
+        assert_eq!(world.get::<Health>(entity), Some(&Health(100)));
+
+        // `Grounded` was inserted before entering `Walk`, so leaving it keeps `Grounded`
+        world.entity_mut(entity).insert(Grounded);
+        world.entity_mut(entity).insert(TestRequired::Walk);
+        world.entity_mut(entity).remove::<TestRequired>();
+
+        assert!(!world.entity(entity).contains::<Velocity>());
+        assert!(!world.entity(entity).contains::<Gait>());
+        assert!(world.entity(entity).contains::<Grounded>());
+        assert_eq!(world.get::<Health>(entity), Some(&Health(100)));
+
+        // a clone owns the required components it was cloned with
+        let source = world.spawn(TestRequired::Walk).id();
+        let clone = world.entity_mut(source).clone_and_spawn();
+        world.entity_mut(clone).insert(TestRequired::Fall { height: 1.0 });
+
+        assert!(!world.entity(clone).contains::<Grounded>());
+        assert!(!world.entity(clone).contains::<Gait>());
+        assert!(world.entity(source).contains::<Grounded>());
+    }
+
+    #[derive(Component, Debug, PartialEq)]
+    struct AttackPower(u32);
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Cooldown(f32);
+
+    fn make_attack_bundle(value: &TestBundled) -> impl Bundle + use<> {
+        // only called when entering `Attack`
+        let power = match value {
+            TestBundled::Attack { power } => *power,
+            _ => 0,
+        };
+        (AttackPower(power), Cooldown(0.5))
+    }
+
+    #[allow(unused)]
+    #[derive(Clone, Debug, EnumComponent)]
+    enum TestBundled {
+        #[enum_component(bundle = make_attack_bundle)]
+        Attack {
+            power: u32,
+        },
+        #[enum_component(bundle = |_: &TestBundled| Cooldown(1.0))]
+        Rest,
+        Idle,
+    }
+
+    #[test]
+    fn test_bundle() {
+        let mut world = World::new();
+        let entity = world.spawn(TestBundled::Attack { power: 3 }).id();
+
+        assert_eq!(world.get::<AttackPower>(entity), Some(&AttackPower(3)));
+        assert_eq!(world.get::<Cooldown>(entity), Some(&Cooldown(0.5)));
+
+        world.entity_mut(entity).insert(TestBundled::Rest);
+
+        assert!(!world.entity(entity).contains::<AttackPower>());
+        assert_eq!(world.get::<Cooldown>(entity), Some(&Cooldown(1.0)));
+
+        world.entity_mut(entity).insert(TestBundled::Idle);
+
+        assert!(!world.entity(entity).contains::<Cooldown>());
+
+        world.entity_mut(entity).insert(TestBundled::Attack { power: 7 });
+        assert_eq!(world.get::<AttackPower>(entity), Some(&AttackPower(7)));
+
+        world.entity_mut(entity).remove::<TestBundled>();
+
+        assert!(!world.entity(entity).contains::<AttackPower>());
+        assert!(!world.entity(entity).contains::<Cooldown>());
+    }
+
+    #[allow(unused)]
+    #[derive(Clone, Debug, PartialEq, EnumComponent)]
+    enum TestEntities {
+        Idle,
+        Target(#[enum_component(entities)] Entity),
+        Follow {
+            #[enum_component(entities)]
+            leader: Entity,
+            distance: f32,
+        },
+        Pair(u8, #[enum_component(entities)] Entity),
+    }
+
+    #[test]
+    fn test_map_entities() {
+        let mut world = World::new();
+        let (from, to) = (world.spawn_empty().id(), world.spawn_empty().id());
+
+        let mapped = |mut value: TestEntities| {
+            <TestEntities as Component>::map_entities(&mut value, &mut (from, to));
+            value
+        };
+
+        assert_eq!(mapped(TestEntities::Idle), TestEntities::Idle);
+        assert_eq!(mapped(TestEntities::Target(from)), TestEntities::Target(to));
+        assert_eq!(
+            mapped(TestEntities::Follow { leader: from, distance: 1.0 }),
+            TestEntities::Follow { leader: to, distance: 1.0 }
+        );
+        assert_eq!(mapped(TestEntities::Pair(1, from)), TestEntities::Pair(1, to));
+
+        // cloning maps the source entity to its clone, and leaves the other entities alone
+        let source = world.spawn_empty().id();
+        world.entity_mut(source).insert(TestEntities::Follow { leader: source, distance: 2.0 });
+        let clone = world.entity_mut(source).clone_and_spawn();
+
+        assert_eq!(
+            world.get::<TestEntities>(clone),
+            Some(&TestEntities::Follow { leader: clone, distance: 2.0 })
+        );
+        assert!(world.entity(clone).contains::<Enum!(TestEntities::Follow)>());
+
+        world.entity_mut(source).insert(TestEntities::Target(from));
+        let clone = world.entity_mut(source).clone_and_spawn();
+
+        assert_eq!(world.get::<TestEntities>(clone), Some(&TestEntities::Target(from)));
+    }
+
+    #[derive(Resource, Default)]
+    struct MarkerEvents(usize);
+
+    #[allow(unused)]
+    #[derive(Clone, Debug, EnumComponent)]
+    #[enum_component(clone_behavior = Ignore)]
+    enum TestUncloned {
+        A,
+        B(u8),
+    }
+
+    #[test]
+    fn test_clone() {
+        fn count<M: Component>(_: On<Insert, M>, mut events: ResMut<MarkerEvents>) {
+            events.0 += 1;
+        }
+
+        let mut world = World::new();
+        world.init_resource::<MarkerEvents>();
+        world.add_observer(count::<A>);
+        world.add_observer(count::<B>);
+        world.add_observer(count::<C>);
+
+        for value in [TestEnum::A, TestEnum::B { v: 1.0 }, TestEnum::C(42)] {
+            let source = world.spawn(value.clone()).id();
+            world.resource_mut::<MarkerEvents>().0 = 0;
+
+            let clone = world.entity_mut(source).clone_and_spawn();
+            assert_eq!(world.resource::<MarkerEvents>().0, 1);
+
+            let target = world.spawn(TestEnum::C(0)).id();
+            world.resource_mut::<MarkerEvents>().0 = 0;
+            world.entity_mut(source).clone_with_opt_out(target, |_| {});
+            assert_eq!(world.resource::<MarkerEvents>().0, 1);
+
+            for entity in [clone, target] {
+                let entity = world.entity(entity);
+                assert_eq!(entity.contains::<A>(), matches!(value, TestEnum::A));
+                assert_eq!(entity.contains::<B>(), matches!(value, TestEnum::B { .. }));
+                assert_eq!(entity.contains::<C>(), matches!(value, TestEnum::C(_)));
+            }
+        }
+
+        let source = world.spawn(TestUncloned::B(1)).id();
+        let clone = world.entity_mut(source).clone_and_spawn();
+        assert!(!world.entity(clone).contains::<TestUncloned>());
+        assert!(!world.entity(clone).contains::<test_uncloned_filters::B>());
+    }
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Waypoints(usize);
+
+    fn make_waypoints(value: &TestUnclonable) -> impl Bundle + use<> {
+        // only called when entering `Path`
+        Waypoints(match value {
+            TestUnclonable::Path(path) => path.len(),
+            _ => 0,
+        })
+    }
+
+    // deliberately not Clone: the hooks only borrow the value to find its variant
+    #[allow(unused)]
+    #[derive(Debug, EnumComponent)]
+    enum TestUnclonable {
+        Idle,
+        #[enum_component(bundle = make_waypoints)]
+        Path(Vec<[f32; 3]>),
+    }
+
+    #[test]
+    fn test_without_clone() {
+        use test_unclonable_filters::{Idle, Path};
+
+        assert_eq!(TestUnclonable::Idle.variant_index(), 0);
+        assert_eq!(TestUnclonable::Path(vec![]).variant_index(), 1);
+
+        let mut world = World::new();
+        let entity = world.spawn(TestUnclonable::Path(vec![[0.0; 3]; 3])).id();
+
+        assert!(world.entity(entity).contains::<Path>());
+        assert_eq!(world.get::<Waypoints>(entity), Some(&Waypoints(3)));
+
+        world.entity_mut(entity).insert(TestUnclonable::Idle);
+
+        assert!(world.entity(entity).contains::<Idle>());
+        assert!(!world.entity(entity).contains::<Path>());
+        assert!(!world.entity(entity).contains::<Waypoints>());
+    }
+
+    // tests run on threads of their own, and a world applies its commands on the thread that owns it
+    thread_local! {
+        static ERRORS: std::cell::RefCell<Vec<EnumFilterError>> = const { std::cell::RefCell::new(Vec::new()) };
+    }
+
+    fn record_error(error: BevyError, _: ErrorContext) {
+        ERRORS.with_borrow_mut(|errors| errors.extend(error.downcast_ref::<EnumFilterError>().cloned()));
+    }
+
+    fn take_errors() -> Vec<EnumFilterError> {
+        ERRORS.take()
+    }
+
+    fn queue_despawn(mut world: DeferredWorld, context: HookContext) {
+        world.commands().entity(context.entity).despawn();
+    }
+
+    // the despawn is queued before the marker insertion of on_insert
+    #[allow(unused)]
+    #[derive(Debug, EnumComponent)]
+    #[enum_component(on_add = queue_despawn)]
+    enum TestDespawnedEarly {
+        A,
+    }
+
+    // replaced before the enum when inserted in front of it, so the despawn is queued before the marker removal
+    #[derive(Component)]
+    #[component(on_replace = queue_despawn)]
+    struct Despawner;
+
+    #[allow(unused)]
+    #[derive(Debug, EnumComponent)]
+    enum TestDespawnedLate {
+        A,
+        B,
+    }
+
+    #[test]
+    fn test_hook_errors() {
+        let mut world = World::new();
+        world.insert_resource(DefaultErrorHandler(record_error));
+
+        let entity = world.spawn(TestDespawnedEarly::A).id();
+
+        assert!(world.get_entity(entity).is_err());
+        let error = EnumFilterError {
+            enum_name: "TestDespawnedEarly",
+            entity,
+            origin: EnumFilterErrorOrigin::Hook("on_insert"),
+            kind: EnumFilterErrorKind::EntityDespawned,
+        };
+        assert_eq!(take_errors(), std::slice::from_ref(&error));
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "`on_insert` hook of `TestDespawnedEarly` failed for entity {entity}: the entity no longer exists"
+            )
+        );
+
+        // the removal of the markers reports the despawn the same way as their insertion
+        let entity = world.spawn((Despawner, TestDespawnedLate::A)).id();
+        world.entity_mut(entity).insert((Despawner, TestDespawnedLate::B));
+
+        assert!(world.get_entity(entity).is_err());
+        let error = |hook| EnumFilterError {
+            enum_name: "TestDespawnedLate",
+            entity,
+            origin: EnumFilterErrorOrigin::Hook(hook),
+            kind: EnumFilterErrorKind::EntityDespawned,
+        };
+        assert_eq!(take_errors(), [error("on_replace"), error("on_insert")]);
+    }
+
+    #[allow(unused)]
+    #[derive(Debug, EnumComponent)]
+    #[enum_component(transitions(Idle -> Walk, Walk -> Run, _ -> Idle))]
+    enum TestGraph {
+        Idle,
+        Walk,
+        Run(f32),
+    }
+
+    #[derive(Resource, Default)]
+    struct StrictCalls(Vec<(&'static str, TestStrictGraph)>);
+
+    fn record_strict(name: &'static str) -> impl Fn(DeferredWorld, HookContext) {
+        move |mut world, HookContext { entity, .. }| {
+            let value = world.get::<TestStrictGraph>(entity).unwrap().clone();
+            world.resource_mut::<StrictCalls>().0.push((name, value));
+        }
+    }
+
+    #[allow(unused)]
+    #[derive(Clone, Debug, PartialEq, EnumComponent)]
+    #[enum_component(strict_transitions(Idle -> Walk, Walk -> Run, _ -> Idle))]
+    #[enum_component(on_insert = record_strict("insert"), on_replace = record_strict("replace"))]
+    enum TestStrictGraph {
+        Idle,
+        Walk,
+        Run(f32),
+    }
+
+    #[test]
+    fn test_transitions() {
+        assert!(TestGraph::allows_transition(0, 1));
+        assert!(TestGraph::allows_transition(2, 0));
+        assert!(TestGraph::allows_transition(2, 2));
+        assert!(!TestGraph::allows_transition(0, 2));
+
+        let mut world = World::new();
+        world.insert_resource(DefaultErrorHandler(record_error));
+
+        let entity = world.spawn(TestGraph::Idle).id();
+        world.entity_mut(entity).insert(TestGraph::Walk);
+        world.entity_mut(entity).insert(TestGraph::Run(1.0));
+        world.entity_mut(entity).insert(TestGraph::Run(2.0));
+        assert_eq!(take_errors(), []);
+
+        world.entity_mut(entity).remove::<TestGraph>();
+        world.entity_mut(entity).insert(TestGraph::Walk);
+        world.entity_mut(entity).insert(TestGraph::Idle);
+        world.entity_mut(entity).insert(TestGraph::Run(3.0));
+
+        // reported, but the illegal transition still happens
+        let kind = EnumFilterErrorKind::IllegalTransition { from: "Idle", to: "Run" };
+        let origin = EnumFilterErrorOrigin::Transitions;
+        let error = EnumFilterError { enum_name: "TestGraph", entity, origin, kind };
+        assert_eq!(take_errors(), std::slice::from_ref(&error));
+        assert!(world.entity(entity).contains::<test_graph_filters::Run>());
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "entity {entity} broke the transitions of `TestGraph`: illegal transition from `Idle` to `Run`"
+            )
+        );
+
+        world.init_resource::<StrictCalls>();
+        let entity = world.spawn((TestStrictGraph::Idle, VariantSince::<TestStrictGraph>::default())).id();
+        let since = world.get::<VariantSince<TestStrictGraph>>(entity).unwrap().tick();
+        world.clear_trackers();
+        world.entity_mut(entity).insert(TestStrictGraph::Run(1.0));
+
+        assert_eq!(world.get::<TestStrictGraph>(entity), Some(&TestStrictGraph::Idle));
+        assert!(world.entity(entity).contains::<test_strict_graph_filters::Idle>());
+        assert!(!world.entity(entity).contains::<test_strict_graph_filters::Run>());
+        assert_eq!(take_errors(), []);
+        // the entity never left `Idle`, so it does not enter it again either
+        let mut added = world.query_filtered::<Entity, Added<Enum!(TestStrictGraph::Idle)>>();
+        assert_eq!(added.iter(&world).count(), 0);
+        let mut entered = world.query_filtered::<Entity, JustEntered<Enum!(TestStrictGraph::Idle)>>();
+        assert_eq!(entered.iter(&world).count(), 0);
+        assert_eq!(world.get::<VariantSince<TestStrictGraph>>(entity).unwrap().tick(), since);
+        // the user's hooks never see the illegal value, only `Idle` replaced by itself
+        assert_eq!(
+            std::mem::take(&mut world.resource_mut::<StrictCalls>().0),
+            [
+                ("insert", TestStrictGraph::Idle),
+                ("replace", TestStrictGraph::Idle),
+                ("insert", TestStrictGraph::Idle)
+            ]
+        );
+
+        world.entity_mut(entity).insert(TestStrictGraph::Walk);
+        world.entity_mut(entity).insert(TestStrictGraph::Run(1.0));
+
+        assert_eq!(world.get::<TestStrictGraph>(entity), Some(&TestStrictGraph::Run(1.0)));
+        assert!(world.entity(entity).contains::<test_strict_graph_filters::Run>());
+        assert!(!world.entity(entity).contains::<test_strict_graph_filters::Walk>());
+    }
+
+    #[test]
+    fn test_despawn() {
+        let mut world = World::new();
+        // a marker command queued while despawning would fail to find the entity
+        world.insert_resource(DefaultErrorHandler(record_error));
+        world.init_resource::<MarkerEvents>();
+        world.add_observer(|_: On<Remove, C>, mut events: ResMut<MarkerEvents>| events.0 += 1);
+
+        let entity = world.spawn(TestEnum::C(1)).id();
+        world.entity_mut(entity).despawn();
+
+        assert!(world.get_entity(entity).is_err());
+        assert_eq!(take_errors(), []);
+        assert_eq!(world.resource::<MarkerEvents>().0, 1);
+
+        let parent = world
+            .spawn((
+                TestEnum::A,
+                children![TestEnum::B { v: 1.0 }, (TestEnum::C(2), children![TestEnum::C(3)])],
+            ))
+            .id();
+        world.entity_mut(parent).despawn();
+
+        assert_eq!(world.query::<&TestEnum>().iter(&world).len(), 0);
+        assert_eq!(world.query_filtered::<(), Or<(With<A>, With<B>, With<C>)>>().iter(&world).len(), 0);
+        assert_eq!(world.resource::<MarkerEvents>().0, 3);
+        assert_eq!(take_errors(), []);
+
+        world.spawn_batch([TestEnum::A, TestEnum::B { v: 1.0 }, TestEnum::C(4)]);
+        world.clear_entities();
+
+        assert_eq!(world.query_filtered::<(), Or<(With<A>, With<B>, With<C>)>>().iter(&world).len(), 0);
+        assert_eq!(take_errors(), []);
+
+        let entity = world.spawn(TestEnum::B { v: 2.0 }).id();
+        assert!(world.entity(entity).contains::<B>());
+
+        // removing the enum leaves none of its bookkeeping behind
+        world.entity_mut(entity).remove::<TestEnum>();
+        assert!(!world.entity(entity).contains::<EnumState<TestEnum>>());
+        assert_eq!(take_errors(), []);
+    }
+
+    // nothing from the generated modules is imported here
+    mod resolution {
+        use super::super::prelude::*;
+        #[cfg(feature = "bevy")]
+        use bevy::prelude::*;
+        #[cfg(not(feature = "bevy"))]
+        use bevy_ecs::prelude::*;
+
+        mod nested {
+            #[allow(unused)]
+            #[derive(Debug, crate::EnumComponent)]
+            pub enum Aliased {
+                A,
+                B(u8),
+            }
+        }
+
+        pub use nested::Aliased as Renamed;
+        type Alias = nested::Aliased;
+
+        impl nested::Aliased {
+            fn count_b(world: &mut World) -> usize {
+                world.query_filtered::<(), With<Enum!(Self::B)>>().iter(world).len()
+            }
+        }
+
+        #[test]
+        fn test_resolution() {
+            let mut world = World::new();
+            world.spawn(nested::Aliased::B(1));
+
+            assert_eq!(world.query_filtered::<(), With<Enum!(nested::Aliased::B)>>().iter(&world).len(), 1);
+            assert_eq!(world.query_filtered::<(), With<Enum!(Renamed::B)>>().iter(&world).len(), 1);
+            assert_eq!(world.query_filtered::<(), With<Enum!(Alias::B)>>().iter(&world).len(), 1);
+            assert_eq!(world.query_filtered::<(), With<Enum!(self::Alias::A)>>().iter(&world).len(), 0);
+            assert_eq!(nested::Aliased::count_b(&mut world), 1);
+        }
+    }
+
     #[test]
     fn test_abbr() {
         let mut world = World::new();
@@ -119,4 +1092,36 @@ mod tests {
         assert!(world.query_filtered::<Entity, Added<Enum!(TestEnum::C)>>().single(&world).is_ok());
         assert!(world.query_filtered::<Entity, Changed<Enum!(TestEnum::C)>>().single(&world).is_ok());
     }
+
+    fn assert_matching<F: QueryFilter>(world: &mut World, expected: &[Entity]) {
+        let mut entities = world.query_filtered::<Entity, F>().iter(world).collect::<Vec<_>>();
+        let mut expected = expected.to_vec();
+        entities.sort();
+        expected.sort();
+        assert_eq!(entities, expected);
+    }
+
+    #[test]
+    fn test_combined_filter() {
+        let mut world = World::new();
+        let ab = world.spawn((TestEnum::A, TestReexported::B(1))).id();
+        let cb = world.spawn((TestEnum::C(1), TestReexported::B(2))).id();
+        let ba = world.spawn((TestEnum::B { v: 1.0 }, TestReexported::A)).id();
+
+        assert_matching::<Enum!(TestEnum::A, TestReexported::B)>(&mut world, &[ab]);
+        assert_matching::<Enum!(TestEnum::A | TestEnum::C, TestReexported::B)>(&mut world, &[ab, cb]);
+        assert_matching::<Enum!(!TestEnum::A, (TestReexported::B | TestShorthand::A))>(&mut world, &[cb]);
+        assert_matching::<Enum!(!(TestEnum::A | TestEnum::C))>(&mut world, &[ba]);
+        assert_matching::<Enum!(!(TestEnum::C, TestReexported::B), TestReexported::B)>(&mut world, &[ab]);
+        assert_matching::<Enum!(TestEnum::A & TestReexported::B | TestReexported::A)>(&mut world, &[ab, ba]);
+        assert_matching::<Enum!(TestEnum::A & (TestReexported::B | TestReexported::A))>(&mut world, &[ab]);
+
+        // parenthesized, a single variant is a filter rather than the marker
+        let _: std::marker::PhantomData<With<A>> = std::marker::PhantomData::<Enum!((TestEnum::A))>;
+        assert_matching::<Enum!((TestEnum::A))>(&mut world, &[ab]);
+
+        // and so is a list of one with a trailing comma
+        let _: std::marker::PhantomData<With<A>> = std::marker::PhantomData::<Enum!(TestEnum::A,)>;
+        assert_matching::<Enum!(TestEnum::A,)>(&mut world, &[ab]);
+    }
 }
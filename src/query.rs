@@ -0,0 +1,527 @@
+use crate::EnumComponent;
+#[cfg(feature = "bevy")]
+use bevy::ecs::{
+    component::ComponentId,
+    entity::Entity,
+    query::{QueryBuilder, QueryData, QueryFilter, QueryState},
+    resource::Resource,
+    world::World,
+};
+#[cfg(not(feature = "bevy"))]
+use bevy_ecs::{
+    component::ComponentId,
+    entity::Entity,
+    query::{QueryBuilder, QueryData, QueryFilter, QueryState},
+    resource::Resource,
+    world::World,
+};
+use std::{any::TypeId, fmt, str::FromStr};
+
+/// Every [`EnumComponent`] that can be referred to by a [`VariantFilter`].
+///
+/// Enums are added with [`EnumFilterWorldExt::register_enum_component`].
+#[derive(Resource, Default, Debug)]
+pub struct EnumFilterRegistry {
+    enums: Vec<RegisteredEnum>,
+}
+
+#[derive(Debug)]
+struct RegisteredEnum {
+    type_id: TypeId,
+    type_path: &'static str,
+    name: &'static str,
+    variants: &'static [&'static str],
+    markers: Vec<ComponentId>,
+}
+
+impl EnumFilterRegistry {
+    /// Returns the marker component of `variant`, where `path` is the path to the enum,
+    /// written the same way as in front of the variant in an [`Enum!`](crate::Enum) path.
+    pub fn marker_id(&self, path: &[impl AsRef<str>], variant: &str) -> Result<ComponentId, VariantFilterError> {
+        let display = path.iter().map(AsRef::as_ref).collect::<Vec<_>>().join("::");
+        let Some((name, _)) = path.split_last() else {
+            return Err(VariantFilterError::UnknownEnum(display));
+        };
+
+        let mut candidates = self.enums.iter().filter(|registered| {
+            registered.name == name.as_ref()
+                && (path.len() == 1
+                    || registered.type_path == display
+                    || registered.type_path.ends_with(&format!("::{display}")))
+        });
+
+        let registered = match (candidates.next(), candidates.next()) {
+            (Some(registered), None) => registered,
+            (None, _) => return Err(VariantFilterError::UnknownEnum(display)),
+            (Some(_), Some(_)) => return Err(VariantFilterError::AmbiguousEnum(display)),
+        };
+
+        registered
+            .variants
+            .iter()
+            .position(|registered_variant| *registered_variant == variant)
+            .map(|index| registered.markers[index])
+            .ok_or_else(|| VariantFilterError::UnknownVariant { enum_name: display, variant: variant.to_string() })
+    }
+}
+
+/// Extension methods on [`World`] for working with [`EnumComponent`]s at runtime.
+pub trait EnumFilterWorldExt {
+    /// Registers `E` and the markers of its variants in the [`EnumFilterRegistry`],
+    /// so that they can be referred to by a [`VariantFilter`].
+    fn register_enum_component<E: EnumComponent>(&mut self) -> &mut Self;
+
+    /// Parses `filter` as a [`VariantFilter`] and builds a query over the matching entities.
+    fn query_variants(&mut self, filter: &str) -> Result<QueryState<Entity>, VariantFilterError>;
+}
+
+impl EnumFilterWorldExt for World {
+    fn register_enum_component<E: EnumComponent>(&mut self) -> &mut Self {
+        let markers = E::register_markers(self);
+        let mut registry = self.get_resource_or_init::<EnumFilterRegistry>();
+
+        if registry.enums.iter().all(|registered| registered.type_id != TypeId::of::<E>()) {
+            registry.enums.push(RegisteredEnum {
+                type_id: TypeId::of::<E>(),
+                type_path: std::any::type_name::<E>(),
+                name: E::NAME,
+                variants: E::VARIANTS,
+                markers,
+            });
+        }
+
+        self
+    }
+
+    fn query_variants(&mut self, filter: &str) -> Result<QueryState<Entity>, VariantFilterError> {
+        filter.parse::<VariantFilter>()?.query_state(self)
+    }
+}
+
+/// A boolean expression over enum variants, parsed from a string at runtime.
+///
+/// The grammar mirrors the [`Enum!`](crate::Enum) macro: a variant is written as `Enum::Variant`
+/// (or `path::to::Enum::Variant`), and variants are combined with `!`, `&`, `|` and parentheses.
+/// `!` binds tighter than `&`, which binds tighter than `|`.
+///
+/// ```ignore
+/// let filter: VariantFilter = "Foo::A & !Bar::B | Foo::C".parse()?;
+/// let mut query = filter.query_state(&mut world)?;
+/// ```
+///
+/// Only enums registered with [`EnumFilterWorldExt::register_enum_component`] can be referred to.
+///
+/// The query is built from the filter rewritten as an `or` of `and`s, whose size grows exponentially with the
+/// number of `|` nested under `&` (or `&` under `!`): `(A | B) & (C | D)` already needs four conjunctions.
+/// Filters needing more than [`MAX_CONJUNCTIONS`](VariantFilter::MAX_CONJUNCTIONS) are rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VariantFilter {
+    /// Matches entities whose enum is in the given variant.
+    Variant {
+        /// The path to the enum, ending with its name.
+        path: Vec<String>,
+        /// The name of the variant.
+        variant: String,
+    },
+    /// Matches entities not matched by the inner filter.
+    Not(Box<VariantFilter>),
+    /// Matches entities matched by every inner filter. The list must not be empty.
+    And(Vec<VariantFilter>),
+    /// Matches entities matched by any inner filter. The list must not be empty.
+    Or(Vec<VariantFilter>),
+}
+
+impl VariantFilter {
+    /// The most conjunctions the filter may expand to, see [`VariantFilterError::TooComplex`].
+    pub const MAX_CONJUNCTIONS: usize = 4096;
+
+    /// Builds a [`QueryState`] over the entities matched by this filter.
+    pub fn query_state(&self, world: &mut World) -> Result<QueryState<Entity>, VariantFilterError> {
+        let disjunction = {
+            let registry = world.get_resource::<EnumFilterRegistry>().ok_or_else(|| self.unknown_enum())?;
+            self.disjunctive_form(registry, false)?
+        };
+
+        let mut builder = QueryBuilder::<Entity>::new(world);
+        match disjunction.as_slice() {
+            [conjunction] => apply_conjunction(&mut builder, conjunction),
+            _ => {
+                builder.or(|builder| {
+                    disjunction.iter().for_each(|conjunction| {
+                        builder.and(|builder| apply_conjunction(builder, conjunction));
+                    })
+                });
+            }
+        }
+
+        Ok(builder.build())
+    }
+
+    fn unknown_enum(&self) -> VariantFilterError {
+        match self {
+            VariantFilter::Variant { path, .. } => VariantFilterError::UnknownEnum(path.join("::")),
+            VariantFilter::Not(inner) => inner.unknown_enum(),
+            VariantFilter::And(list) | VariantFilter::Or(list) => {
+                list.first().map_or(VariantFilterError::EmptyList, Self::unknown_enum)
+            }
+        }
+    }
+
+    /// Resolves the filter into an `or` of `and`s of `(marker, present)` terms,
+    /// which is the shape [`QueryBuilder`] can express.
+    fn disjunctive_form(
+        &self, registry: &EnumFilterRegistry, negated: bool,
+    ) -> Result<Vec<Vec<(ComponentId, bool)>>, VariantFilterError> {
+        match (self, negated) {
+            (VariantFilter::Variant { path, variant }, _) => {
+                Ok(vec![vec![(registry.marker_id(path, variant)?, !negated)]])
+            }
+            (VariantFilter::Not(inner), _) => inner.disjunctive_form(registry, !negated),
+            (VariantFilter::And(list) | VariantFilter::Or(list), _) if list.is_empty() => {
+                Err(VariantFilterError::EmptyList)
+            }
+            (VariantFilter::Or(list), false) | (VariantFilter::And(list), true) => {
+                list.iter().try_fold(vec![], |mut disjunction, filter| {
+                    disjunction.extend(filter.disjunctive_form(registry, negated)?);
+                    Ok(disjunction)
+                })
+            }
+            (VariantFilter::And(list), false) | (VariantFilter::Or(list), true) => {
+                list.iter().try_fold(vec![vec![]], |disjunction, filter| {
+                    let rhs = filter.disjunctive_form(registry, negated)?;
+                    if disjunction.len() * rhs.len() > Self::MAX_CONJUNCTIONS {
+                        return Err(VariantFilterError::TooComplex);
+                    }
+                    Ok(disjunction
+                        .iter()
+                        .flat_map(|lhs| rhs.iter().map(move |rhs| lhs.iter().chain(rhs).copied().collect()))
+                        .collect())
+                })
+            }
+        }
+    }
+}
+
+fn apply_conjunction<D: QueryData, F: QueryFilter>(
+    builder: &mut QueryBuilder<D, F>, conjunction: &[(ComponentId, bool)],
+) {
+    conjunction.iter().for_each(|&(id, present)| {
+        match present {
+            true => builder.with_id(id),
+            false => builder.without_id(id),
+        };
+    });
+}
+
+impl FromStr for VariantFilter {
+    type Err = VariantFilterError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { tokens: tokenize(source)?, position: 0, len: source.len() };
+        let filter = parser.parse_or()?;
+
+        match parser.tokens.get(parser.position) {
+            Some((offset, token)) => {
+                Err(VariantFilterError::UnexpectedToken { offset: *offset, token: token.to_string() })
+            }
+            None => Ok(filter),
+        }
+    }
+}
+
+impl fmt::Display for VariantFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter<'_>, list: &[VariantFilter], separator: &str| {
+            write!(f, "(")?;
+            for (index, filter) in list.iter().enumerate() {
+                if index != 0 {
+                    write!(f, " {separator} ")?;
+                }
+                write!(f, "{filter}")?;
+            }
+            write!(f, ")")
+        };
+
+        match self {
+            VariantFilter::Variant { path, variant } => write!(f, "{}::{variant}", path.join("::")),
+            VariantFilter::Not(inner) => write!(f, "!{inner}"),
+            VariantFilter::And(list) => join(f, list, "&"),
+            VariantFilter::Or(list) => join(f, list, "|"),
+        }
+    }
+}
+
+/// An error produced while parsing or resolving a [`VariantFilter`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VariantFilterError {
+    /// A character that is not part of the grammar was found at the given byte offset.
+    UnexpectedCharacter { offset: usize, character: char },
+    /// A token that does not fit the grammar was found at the given byte offset.
+    UnexpectedToken { offset: usize, token: String },
+    /// The filter ended where more input was expected.
+    UnexpectedEnd { offset: usize },
+    /// No registered enum matches the given path.
+    UnknownEnum(String),
+    /// Several registered enums match the given path. Qualify it with its module path.
+    AmbiguousEnum(String),
+    /// The enum has no variant with the given name.
+    UnknownVariant { enum_name: String, variant: String },
+    /// An [`And`](VariantFilter::And) or [`Or`](VariantFilter::Or) filter was built with an empty list.
+    EmptyList,
+    /// The filter expands to more than [`VariantFilter::MAX_CONJUNCTIONS`] conjunctions.
+    TooComplex,
+}
+
+impl fmt::Display for VariantFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VariantFilterError::UnexpectedCharacter { offset, character } => {
+                write!(f, "unexpected character `{character}` at offset {offset}")
+            }
+            VariantFilterError::UnexpectedToken { offset, token } => {
+                write!(f, "unexpected `{token}` at offset {offset}")
+            }
+            VariantFilterError::UnexpectedEnd { offset } => {
+                write!(f, "unexpected end of filter at offset {offset}, expected `Enum::Variant`, `!` or `(`")
+            }
+            VariantFilterError::UnknownEnum(path) => write!(f, "`{path}` is not a registered EnumComponent"),
+            VariantFilterError::AmbiguousEnum(path) => {
+                write!(f, "`{path}` matches several registered EnumComponents, qualify it with its module path")
+            }
+            VariantFilterError::UnknownVariant { enum_name, variant } => {
+                write!(f, "`{enum_name}` has no variant `{variant}`")
+            }
+            VariantFilterError::EmptyList => write!(f, "an `And` or `Or` filter has no operand"),
+            VariantFilterError::TooComplex => write!(
+                f,
+                "the filter expands to more than {} conjunctions, simplify it",
+                VariantFilter::MAX_CONJUNCTIONS
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VariantFilterError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Ident(&'a str),
+    PathSep,
+    Not,
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "{ident}"),
+            Token::PathSep => write!(f, "::"),
+            Token::Not => write!(f, "!"),
+            Token::And => write!(f, "&"),
+            Token::Or => write!(f, "|"),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token<'_>)>, VariantFilterError> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+
+    while let Some((offset, character)) = chars.next() {
+        let token = match character {
+            character if character.is_whitespace() => continue,
+            '!' => Token::Not,
+            '&' => Token::And,
+            '|' => Token::Or,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ':' if chars.next_if(|(_, next)| *next == ':').is_some() => Token::PathSep,
+            character if character == '_' || character.is_alphabetic() => {
+                let mut end = offset + character.len_utf8();
+                while let Some((next_offset, next)) =
+                    chars.next_if(|(_, next)| *next == '_' || next.is_alphanumeric())
+                {
+                    end = next_offset + next.len_utf8();
+                }
+                Token::Ident(&source[offset..end])
+            }
+            character => return Err(VariantFilterError::UnexpectedCharacter { offset, character }),
+        };
+        tokens.push((offset, token));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(usize, Token<'a>)>,
+    position: usize,
+    len: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Result<(usize, Token<'a>), VariantFilterError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or(VariantFilterError::UnexpectedEnd { offset: self.len })?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn parse_or(&mut self) -> Result<VariantFilter, VariantFilterError> {
+        let mut list = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            list.push(self.parse_and()?);
+        }
+
+        Ok(if list.len() == 1 { list.remove(0) } else { VariantFilter::Or(list) })
+    }
+
+    fn parse_and(&mut self) -> Result<VariantFilter, VariantFilterError> {
+        let mut list = vec![self.parse_unary()?];
+        while self.peek() == Some(&Token::And) {
+            self.position += 1;
+            list.push(self.parse_unary()?);
+        }
+
+        Ok(if list.len() == 1 { list.remove(0) } else { VariantFilter::And(list) })
+    }
+
+    fn parse_unary(&mut self) -> Result<VariantFilter, VariantFilterError> {
+        match self.next()? {
+            (_, Token::Not) => Ok(VariantFilter::Not(Box::new(self.parse_unary()?))),
+            (_, Token::Open) => {
+                let filter = self.parse_or()?;
+                match self.next()? {
+                    (_, Token::Close) => Ok(filter),
+                    (offset, token) => {
+                        Err(VariantFilterError::UnexpectedToken { offset, token: token.to_string() })
+                    }
+                }
+            }
+            (_, Token::Ident(first)) => {
+                let mut segments = vec![first.to_string()];
+                while self.peek() == Some(&Token::PathSep) {
+                    self.position += 1;
+                    match self.next()? {
+                        (_, Token::Ident(segment)) => segments.push(segment.to_string()),
+                        (offset, token) => {
+                            return Err(VariantFilterError::UnexpectedToken { offset, token: token.to_string() });
+                        }
+                    }
+                }
+
+                match segments.len() {
+                    1 => match self.next() {
+                        Ok((offset, token)) => {
+                            Err(VariantFilterError::UnexpectedToken { offset, token: token.to_string() })
+                        }
+                        Err(e) => Err(e),
+                    },
+                    _ => {
+                        let variant = segments.pop().unwrap();
+                        Ok(VariantFilter::Variant { path: segments, variant })
+                    }
+                }
+            }
+            (offset, token) => Err(VariantFilterError::UnexpectedToken { offset, token: token.to_string() }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use std::collections::HashSet;
+
+    #[allow(unused)]
+    #[derive(Clone, Debug, EnumComponent)]
+    enum Foo {
+        A,
+        B(i32),
+        C { v: f64 },
+    }
+
+    #[allow(unused)]
+    #[derive(Clone, Debug, EnumComponent)]
+    enum Bar {
+        A,
+        B,
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "Foo::A & !Bar::B | Foo::C".parse::<VariantFilter>().unwrap().to_string(),
+            "((Foo::A & !Bar::B) | Foo::C)"
+        );
+        assert_eq!(
+            "!(a::Foo::A | Foo::B)".parse::<VariantFilter>().unwrap().to_string(),
+            "!(a::Foo::A | Foo::B)"
+        );
+        assert_eq!("Foo::A &".parse::<VariantFilter>(), Err(VariantFilterError::UnexpectedEnd { offset: 8 }));
+        assert_eq!(
+            "Foo::A Foo::B".parse::<VariantFilter>(),
+            Err(VariantFilterError::UnexpectedToken { offset: 7, token: "Foo".to_string() })
+        );
+        assert_eq!("Foo".parse::<VariantFilter>(), Err(VariantFilterError::UnexpectedEnd { offset: 3 }));
+        assert_eq!(
+            "Foo::A + Foo::B".parse::<VariantFilter>(),
+            Err(VariantFilterError::UnexpectedCharacter { offset: 7, character: '+' })
+        );
+    }
+
+    #[test]
+    fn test_query_variants() {
+        let mut world = World::new();
+        world.register_enum_component::<Foo>().register_enum_component::<Bar>();
+
+        let a = world.spawn(Foo::A).id();
+        let a_b = world.spawn((Foo::A, Bar::B)).id();
+        let b_a = world.spawn((Foo::B(0), Bar::A)).id();
+        let c_b = world.spawn((Foo::C { v: 0.0 }, Bar::B)).id();
+
+        let matches = |world: &mut World, filter: &str| {
+            let mut query = world.query_variants(filter).unwrap();
+            query.iter(world).collect::<HashSet<_>>()
+        };
+
+        assert_eq!(matches(&mut world, "Foo::A & !Bar::B | Foo::C"), HashSet::from([a, c_b]));
+        assert_eq!(matches(&mut world, "Bar::B"), HashSet::from([a_b, c_b]));
+        assert_eq!(matches(&mut world, "!(Foo::A | Bar::A)"), HashSet::from([c_b]));
+        assert_eq!(matches(&mut world, "(Foo::B | Foo::C) & (Bar::A | Bar::B)"), HashSet::from([b_a, c_b]));
+
+        assert_eq!(
+            world.query_variants("Baz::A").err(),
+            Some(VariantFilterError::UnknownEnum("Baz".to_string()))
+        );
+        assert_eq!(
+            world.query_variants("Foo::D").err(),
+            Some(VariantFilterError::UnknownVariant { enum_name: "Foo".to_string(), variant: "D".to_string() })
+        );
+
+        assert_eq!(
+            VariantFilter::Or(vec![]).query_state(&mut world).err(),
+            Some(VariantFilterError::EmptyList)
+        );
+        let negated_empty = VariantFilter::Not(Box::new(VariantFilter::And(vec![])));
+        assert_eq!(negated_empty.query_state(&mut World::new()).err(), Some(VariantFilterError::EmptyList));
+
+        // 2^13 conjunctions
+        let too_complex = vec!["(Foo::A | Foo::B)"; 13].join(" & ");
+        assert_eq!(world.query_variants(&too_complex).err(), Some(VariantFilterError::TooComplex));
+    }
+}
@@ -0,0 +1,158 @@
+#[cfg(feature = "bevy")]
+use bevy::ecs::{
+    change_detection::DetectChanges,
+    component::Component,
+    resource::Resource,
+    system::{Local, Res},
+};
+#[cfg(not(feature = "bevy"))]
+use bevy_ecs::{
+    change_detection::DetectChanges,
+    component::Component,
+    resource::Resource,
+    system::{Local, Res},
+};
+
+/// Implemented by the [`EnumResource`](derive@crate::EnumResource) derive on enums stored as a resource,
+/// whose variants are then checked with the run conditions [`in_variant`], [`entered_variant`] and
+/// [`exited_variant`].
+pub trait EnumResource: Resource {
+    /// The name of the enum, as written in front of a variant in an [`Enum!`](crate::Enum) path.
+    const NAME: &'static str;
+    /// The names of the enum's variants, in declaration order.
+    const VARIANTS: &'static [&'static str];
+
+    /// Returns the index of the active variant in [`VARIANTS`](EnumResource::VARIANTS).
+    fn variant_index(&self) -> usize;
+}
+
+/// Implemented by the markers generated by the [`EnumResource`](derive@crate::EnumResource) derive.
+/// They are never inserted, and only name a variant of the resource.
+pub trait ResourceMarker: Component {
+    /// The resource the marker was generated for.
+    type Resource: EnumResource;
+    /// The index of the marker's variant in [`EnumResource::VARIANTS`].
+    const INDEX: usize;
+}
+
+/// A run condition that is `true` while the resource of `M` exists and is in the variant of `M`.
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use bevy_ecs_enum_filter::{entered_variant, in_variant, prelude::*, EnumResource};
+/// #[derive(Debug, EnumResource)]
+/// enum Weather {
+///     Sunny,
+///     Rain(f32),
+/// }
+///
+/// fn splash() {}
+/// fn open_umbrellas() {}
+///
+/// let mut schedule = Schedule::default();
+/// schedule.add_systems((
+///     splash.run_if(in_variant::<Enum!(Weather::Rain)>()),
+///     open_umbrellas.run_if(entered_variant::<Enum!(Weather::Rain)>()),
+/// ));
+/// ```
+pub fn in_variant<M: ResourceMarker>() -> impl FnMut(Option<Res<M::Resource>>) -> bool + Clone {
+    |resource: Option<Res<M::Resource>>| resource.is_some_and(|resource| resource.variant_index() == M::INDEX)
+}
+
+/// A run condition that is `true` when the resource of `M` went into the variant of `M`, including when
+/// it was inserted in that variant. Changing the payload of the variant does not count.
+///
+/// The variant is compared with the one seen when the condition last ran, and only read again if the
+/// resource changed since.
+pub fn entered_variant<M: ResourceMarker>()
+-> impl FnMut(Option<Res<M::Resource>>, Local<Option<usize>>) -> bool + Clone {
+    |resource: Option<Res<M::Resource>>, mut last: Local<Option<usize>>| {
+        transition(resource, &mut last).is_some_and(|(_, current)| current == Some(M::INDEX))
+    }
+}
+
+/// A run condition that is `true` when the resource of `M` left the variant of `M`, including when it was
+/// removed. Changing the payload of the variant does not count.
+///
+/// The variant is compared with the one seen when the condition last ran, and only read again if the
+/// resource changed since.
+pub fn exited_variant<M: ResourceMarker>()
+-> impl FnMut(Option<Res<M::Resource>>, Local<Option<usize>>) -> bool + Clone {
+    |resource: Option<Res<M::Resource>>, mut last: Local<Option<usize>>| {
+        transition(resource, &mut last).is_some_and(|(previous, _)| previous == Some(M::INDEX))
+    }
+}
+
+/// Records the variant of `resource` in `last`, returning the previous and current variants if they differ.
+fn transition<R: EnumResource>(
+    resource: Option<Res<R>>, last: &mut Option<usize>,
+) -> Option<(Option<usize>, Option<usize>)> {
+    let current = match resource {
+        Some(resource) if !resource.is_changed() => return None,
+        resource => resource.map(|resource| resource.variant_index()),
+    };
+    let previous = std::mem::replace(last, current);
+    (previous != current).then_some((previous, current))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    #[cfg(feature = "bevy")]
+    use bevy::ecs::{
+        schedule::{IntoScheduleConfigs, Schedule},
+        system::ResMut,
+        world::World,
+    };
+    #[cfg(not(feature = "bevy"))]
+    use bevy_ecs::{
+        schedule::{IntoScheduleConfigs, Schedule},
+        system::ResMut,
+        world::World,
+    };
+
+    #[allow(unused)]
+    #[derive(Debug, EnumResource)]
+    enum Weather {
+        Sunny,
+        Rain(f32),
+    }
+
+    #[derive(Resource, Default)]
+    struct Runs(Vec<&'static str>);
+
+    #[test]
+    fn test_resource_conditions() {
+        let mut world = World::new();
+        world.init_resource::<Runs>();
+        let mut schedule = Schedule::default();
+        let log = |name: &'static str| move |mut runs: ResMut<Runs>| runs.0.push(name);
+        schedule.add_systems(
+            (
+                log("in").run_if(in_variant::<Enum!(Weather::Rain)>()),
+                log("entered").run_if(entered_variant::<Enum!(Weather::Rain)>()),
+                log("exited").run_if(exited_variant::<Enum!(Weather::Rain)>()),
+            )
+                .chain(),
+        );
+
+        let mut run = |world: &mut World| {
+            schedule.run(world);
+            std::mem::take(&mut world.resource_mut::<Runs>().0)
+        };
+
+        assert!(run(&mut world).is_empty());
+        world.insert_resource(Weather::Rain(0.5));
+        assert_eq!(run(&mut world), ["in", "entered"]);
+        assert_eq!(run(&mut world), ["in"]);
+        *world.resource_mut::<Weather>() = Weather::Rain(0.8);
+        assert_eq!(run(&mut world), ["in"]);
+        *world.resource_mut::<Weather>() = Weather::Sunny;
+        assert_eq!(run(&mut world), ["exited"]);
+        world.insert_resource(Weather::Rain(0.1));
+        assert_eq!(run(&mut world), ["in", "entered"]);
+        world.remove_resource::<Weather>();
+        assert_eq!(run(&mut world), ["exited"]);
+    }
+}
@@ -0,0 +1,304 @@
+use crate::{EnumFilterError, EnumFilterErrorKind, EnumFilterErrorOrigin};
+#[cfg(feature = "bevy")]
+use bevy::ecs::{
+    component::{Component, ComponentId, Immutable, RequiredComponentsRegistrator, StorageType},
+    entity::Entity,
+    lifecycle::ComponentHook,
+    world::{DeferredWorld, EntityWorldMut, World},
+};
+#[cfg(not(feature = "bevy"))]
+use bevy_ecs::{
+    component::{Component, ComponentId, Immutable, RequiredComponentsRegistrator, StorageType},
+    entity::Entity,
+    lifecycle::ComponentHook,
+    world::{DeferredWorld, EntityWorldMut, World},
+};
+use std::{fmt, marker::PhantomData};
+
+/// Implemented by the [`EnumSetComponent`](derive@crate::EnumSetComponent) derive on enums of unit
+/// variants used as flags, any number of which can be active at once in an [`EnumSet`].
+pub trait EnumSetComponent: Sized + Send + Sync + 'static {
+    /// The name of the enum, as written in front of a variant in an [`Enum!`](crate::Enum) path.
+    const NAME: &'static str;
+    /// The names of the enum's variants, in declaration order. There are at most 64 of them.
+    const VARIANTS: &'static [&'static str];
+
+    /// Returns the index of the variant in [`VARIANTS`](EnumSetComponent::VARIANTS).
+    fn variant_index(&self) -> usize;
+
+    /// Returns the variant at `index` in [`VARIANTS`](EnumSetComponent::VARIANTS), if any.
+    fn from_variant_index(index: usize) -> Option<Self>;
+
+    /// Inserts the marker of every variant in `bits` on `entity` and removes the others.
+    #[doc(hidden)]
+    fn sync_markers(entity: &mut EntityWorldMut, bits: u64);
+}
+
+/// A component holding any number of variants of `E` as a bitset, with the marker of each one on the entity,
+/// so that `With<Enum!(Status::Burning)>` works as for an [`EnumComponent`](crate::EnumComponent).
+///
+/// The set is immutable: insert a new one, or change it with [`EntityWorldMut::modify_component`].
+/// The markers are then inserted and removed by a command, only for the variants that were added to or
+/// removed from the set, so the markers of the variants that stay keep their `Added` tick.
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use bevy_ecs_enum_filter::{prelude::*, EnumSet};
+/// #[derive(Debug, EnumSetComponent)]
+/// enum Status {
+///     Poisoned,
+///     Burning,
+///     Slowed,
+/// }
+///
+/// let mut world = World::new();
+/// let entity = world.spawn(EnumSet::from_iter([Status::Poisoned, Status::Burning])).id();
+/// world
+///     .entity_mut(entity)
+///     .modify_component(|status: &mut EnumSet<Status>| status.remove(&Status::Poisoned));
+/// world.flush();
+///
+/// assert!(world.entity(entity).contains::<Enum!(Status::Burning)>());
+/// assert!(!world.entity(entity).contains::<Enum!(Status::Poisoned)>());
+/// ```
+pub struct EnumSet<E: EnumSetComponent> {
+    bits: u64,
+    marker: PhantomData<fn() -> E>,
+}
+
+impl<E: EnumSetComponent> EnumSet<E> {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self::from_bits(0)
+    }
+
+    /// Creates a set from its bits, where the bit `i` stands for the variant at `i` in
+    /// [`VARIANTS`](EnumSetComponent::VARIANTS). Bits without a variant are ignored.
+    pub fn from_bits(bits: u64) -> Self {
+        let mask = match E::VARIANTS.len() {
+            64.. => u64::MAX,
+            len => (1 << len) - 1,
+        };
+        Self { bits: bits & mask, marker: PhantomData }
+    }
+
+    /// Returns the bits of the set.
+    pub fn bits(&self) -> u64 {
+        self.bits
+    }
+
+    /// Returns the set with `variant` added.
+    pub fn with(mut self, variant: E) -> Self {
+        self.insert(variant);
+        self
+    }
+
+    /// Adds `variant`, returning `true` if it was not in the set yet.
+    pub fn insert(&mut self, variant: E) -> bool {
+        let bit = 1 << variant.variant_index();
+        let inserted = self.bits & bit == 0;
+        self.bits |= bit;
+        inserted
+    }
+
+    /// Removes `variant`, returning `true` if it was in the set.
+    pub fn remove(&mut self, variant: &E) -> bool {
+        let bit = 1 << variant.variant_index();
+        let removed = self.bits & bit != 0;
+        self.bits &= !bit;
+        removed
+    }
+
+    /// Returns `true` if `variant` is in the set.
+    pub fn contains(&self, variant: &E) -> bool {
+        self.bits & 1 << variant.variant_index() != 0
+    }
+
+    /// Iterates over the variants in the set, in declaration order.
+    pub fn iter(&self) -> impl Iterator<Item = E> + use<E> {
+        let bits = self.bits;
+        (0..E::VARIANTS.len())
+            .filter(move |index| bits & 1 << index != 0)
+            .filter_map(E::from_variant_index)
+    }
+
+    /// Returns the number of variants in the set.
+    pub fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    /// Returns `true` if no variant is in the set.
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Removes every variant.
+    pub fn clear(&mut self) {
+        self.bits = 0;
+    }
+
+    /// Queues the command that brings the markers of `entity` in line with its set, or removes them all
+    /// if the set was removed, for the hook `hook`.
+    fn sync(world: &mut DeferredWorld, entity: Entity, hook: &'static str) {
+        world.commands().queue(move |world: &mut World| {
+            let mut entity_mut = world.get_entity_mut(entity).map_err(|_| EnumFilterError {
+                enum_name: E::NAME,
+                entity,
+                origin: EnumFilterErrorOrigin::Hook(hook),
+                kind: EnumFilterErrorKind::EntityDespawned,
+            })?;
+            let bits = entity_mut.get::<Self>().map_or(0, |set| set.bits);
+            E::sync_markers(&mut entity_mut, bits);
+            if bits == 0 && !entity_mut.contains::<Self>() {
+                entity_mut.remove::<SetState<E>>();
+            }
+            Ok::<(), EnumFilterError>(())
+        });
+    }
+}
+
+/// Required by [`EnumSet`]: set by its `on_despawn` hook, which runs before `on_remove` when the entity
+/// is despawned, so that `on_remove` leaves alone the markers that are about to disappear with it.
+#[derive(Component)]
+#[component(clone_behavior = Ignore)]
+struct SetState<E: EnumSetComponent> {
+    despawning: bool,
+    marker: PhantomData<fn() -> E>,
+}
+
+impl<E: EnumSetComponent> Default for SetState<E> {
+    fn default() -> Self {
+        Self { despawning: false, marker: PhantomData }
+    }
+}
+
+impl<E: EnumSetComponent> Component for EnumSet<E> {
+    const STORAGE_TYPE: StorageType = StorageType::Table;
+    type Mutability = Immutable;
+
+    fn register_required_components(
+        _requiree: ComponentId, required_components: &mut RequiredComponentsRegistrator,
+    ) {
+        required_components.register_required::<SetState<E>>(Default::default);
+    }
+
+    fn on_insert() -> Option<ComponentHook> {
+        Some(|mut world, context| Self::sync(&mut world, context.entity, "on_insert"))
+    }
+
+    fn on_remove() -> Option<ComponentHook> {
+        Some(|mut world, context| {
+            let despawning = world.get::<SetState<E>>(context.entity).is_some_and(|state| state.despawning);
+            if !despawning {
+                Self::sync(&mut world, context.entity, "on_remove");
+            }
+        })
+    }
+
+    fn on_despawn() -> Option<ComponentHook> {
+        Some(|mut world, context| {
+            if let Some(mut state) = world.get_mut::<SetState<E>>(context.entity) {
+                state.despawning = true;
+            }
+        })
+    }
+}
+
+impl<E: EnumSetComponent> Default for EnumSet<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: EnumSetComponent> Clone for EnumSet<E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E: EnumSetComponent> Copy for EnumSet<E> {}
+
+impl<E: EnumSetComponent> PartialEq for EnumSet<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits == other.bits
+    }
+}
+
+impl<E: EnumSetComponent> Eq for EnumSet<E> {}
+
+impl<E: EnumSetComponent> fmt::Debug for EnumSet<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = (0..E::VARIANTS.len()).filter(|index| self.bits & 1 << index != 0);
+        f.debug_set().entries(names.map(|index| E::VARIANTS[index])).finish()
+    }
+}
+
+impl<E: EnumSetComponent> FromIterator<E> for EnumSet<E> {
+    fn from_iter<I: IntoIterator<Item = E>>(iter: I) -> Self {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<E: EnumSetComponent> Extend<E> for EnumSet<E> {
+    fn extend<I: IntoIterator<Item = E>>(&mut self, iter: I) {
+        for variant in iter {
+            self.insert(variant);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    #[cfg(feature = "bevy")]
+    use bevy::ecs::{entity::Entity, query::Added};
+    #[cfg(not(feature = "bevy"))]
+    use bevy_ecs::{entity::Entity, query::Added};
+
+    #[derive(Clone, Copy, Debug, PartialEq, EnumSetComponent)]
+    enum Status {
+        Poisoned,
+        Burning,
+        Slowed,
+    }
+
+    #[test]
+    fn test_enum_set() {
+        let set = EnumSet::from_iter([Status::Slowed, Status::Poisoned]);
+        assert_eq!(set.iter().collect::<Vec<_>>(), [Status::Poisoned, Status::Slowed]);
+        assert_eq!(format!("{set:?}"), r#"{"Poisoned", "Slowed"}"#);
+        assert_eq!(EnumSet::<Status>::from_bits(u64::MAX).len(), 3);
+
+        let mut world = World::new();
+        let entity = world.spawn(set).id();
+        assert!(world.entity(entity).contains::<Enum!(Status::Poisoned)>());
+        assert!(!world.entity(entity).contains::<Enum!(Status::Burning)>());
+        assert!(world.entity(entity).contains::<Enum!(Status::Slowed)>());
+
+        world.clear_trackers();
+        world.entity_mut(entity).insert(set.with(Status::Burning));
+        let mut added = world.query_filtered::<Entity, Added<Enum!(Status::Slowed)>>();
+        assert_eq!(added.iter(&world).count(), 0);
+        let mut burning = world.query_filtered::<Entity, Enum!(Status::Burning, !Status::Poisoned)>();
+        assert_eq!(burning.iter(&world).count(), 0);
+
+        world
+            .entity_mut(entity)
+            .modify_component(|set: &mut EnumSet<Status>| set.remove(&Status::Poisoned));
+        world.flush();
+        assert_eq!(burning.iter(&world).collect::<Vec<_>>(), [entity]);
+
+        world.entity_mut(entity).remove::<EnumSet<Status>>();
+        assert!(!world.entity(entity).contains::<Enum!(Status::Burning)>());
+        assert!(!world.entity(entity).contains::<Enum!(Status::Slowed)>());
+        assert!(!world.entity(entity).contains::<SetState<Status>>());
+
+        // a command queued while despawning would not find the entity, and the default error handler panics
+        let entity = world.spawn(set).id();
+        world.entity_mut(entity).despawn();
+        assert!(world.get_entity(entity).is_err());
+    }
+}
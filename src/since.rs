@@ -0,0 +1,486 @@
+use crate::{EnumComponent, EnumMarker};
+#[cfg(feature = "bevy")]
+use bevy::{
+    ecs::{
+        archetype::Archetype,
+        change_detection::{DetectChanges, Ref, Tick},
+        component::{Component, ComponentCloneBehavior, ComponentId, Components},
+        entity::{ComponentCloneCtx, Entity, SourceComponent},
+        lifecycle::HookContext,
+        query::{Changed, FilteredAccess, QueryData, QueryFilter, With, WorldQuery},
+        storage::{Table, TableRow},
+        world::{DeferredWorld, World, unsafe_world_cell::UnsafeWorldCell},
+    },
+    time::Time,
+};
+#[cfg(not(feature = "bevy"))]
+use bevy_ecs::{
+    archetype::Archetype,
+    change_detection::{DetectChanges, Ref, Tick},
+    component::{Component, ComponentCloneBehavior, ComponentId, Components},
+    entity::{ComponentCloneCtx, Entity, SourceComponent},
+    lifecycle::HookContext,
+    query::{Changed, FilteredAccess, QueryData, QueryFilter, With, WorldQuery},
+    storage::{Table, TableRow},
+    world::{DeferredWorld, World, unsafe_world_cell::UnsafeWorldCell},
+};
+use std::marker::PhantomData;
+#[cfg(feature = "bevy")]
+use std::time::Duration;
+
+/// When an entity entered its current variant of `E`, kept up to date by the generated hooks.
+///
+/// Like [`VariantHistory`](crate::VariantHistory), it is opt-in: insert it next to the enum on the entities
+/// that need it, usually with [`Default`]. Inserted on its own, it counts from its insertion. It changes
+/// only when the entity enters another variant, not when the same variant is inserted again, which is what
+/// [`JustEntered`] relies on. A cloned entity gets a new one, entering its variant when it is cloned.
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use bevy_ecs_enum_filter::{prelude::*, VariantSince};
+/// #[derive(Debug, EnumComponent)]
+/// enum Foo {
+///     A,
+///     B(f64),
+/// }
+///
+/// let mut world = World::new();
+/// let entity = world.spawn((Foo::A, VariantSince::<Foo>::default())).id();
+/// world.increment_change_tick();
+/// world.entity_mut(entity).insert(Foo::B(1.0));
+///
+/// let since = world.get::<VariantSince<Foo>>(entity).unwrap();
+/// assert_eq!(since.tick(), world.read_change_tick());
+/// ```
+#[derive(Component, Debug)]
+#[component(on_add = Self::added, clone_behavior = Custom(clone_since::<E>))]
+pub struct VariantSince<E: EnumComponent> {
+    tick: Tick,
+    #[cfg(feature = "bevy")]
+    elapsed: Option<Duration>,
+    marker: PhantomData<fn() -> E>,
+}
+
+impl<E: EnumComponent> VariantSince<E> {
+    /// Returns the change tick of the world when the entity entered its current variant.
+    pub fn tick(&self) -> Tick {
+        self.tick
+    }
+
+    /// Returns [`Time::elapsed`] when the entity entered its current variant,
+    /// or `None` if the world had no [`Time`] resource then.
+    #[cfg(feature = "bevy")]
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.elapsed
+    }
+
+    /// Returns how long the entity has been in its current variant according to `time`,
+    /// or `None` if the world had no [`Time`] resource when it entered it.
+    #[cfg(feature = "bevy")]
+    pub fn time_in_variant<T: Default>(&self, time: &Time<T>) -> Option<Duration> {
+        self.elapsed.map(|elapsed| time.elapsed().saturating_sub(elapsed))
+    }
+
+    /// Returns `true` if the entity has been in its current variant for at least `duration` according to `time`.
+    #[cfg(feature = "bevy")]
+    pub fn in_variant_for<T: Default>(&self, time: &Time<T>, duration: Duration) -> bool {
+        self.time_in_variant(time).is_some_and(|in_variant| in_variant >= duration)
+    }
+
+    /// Counts from the insertion, until the hooks of `E` record the next variant entered.
+    fn added(mut world: DeferredWorld, context: HookContext) {
+        Self::enter(&mut world, context.entity);
+    }
+
+    /// Records that `entity` entered a variant just now.
+    pub(crate) fn enter(world: &mut DeferredWorld, entity: Entity) {
+        let tick = world.read_change_tick();
+        #[cfg(feature = "bevy")]
+        let elapsed = world.get_resource::<Time>().map(Time::elapsed);
+
+        if let Some(mut since) = world.get_mut::<Self>(entity) {
+            since.tick = tick;
+            #[cfg(feature = "bevy")]
+            {
+                since.elapsed = elapsed;
+            }
+        }
+    }
+}
+
+/// Clones a fresh [`VariantSince`], which its `on_add` hook sets to the tick of the clone,
+/// unless `E` itself is not cloned.
+fn clone_since<E: EnumComponent>(_source: &SourceComponent, ctx: &mut ComponentCloneCtx) {
+    if !matches!(E::clone_behavior(), ComponentCloneBehavior::Ignore) {
+        ctx.write_target_component(VariantSince::<E>::default());
+    }
+}
+
+impl<E: EnumComponent> Default for VariantSince<E> {
+    fn default() -> Self {
+        Self {
+            tick: Tick::new(0),
+            #[cfg(feature = "bevy")]
+            elapsed: None,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// A [`QueryFilter`](bevy_ecs::query::QueryFilter) for entities that entered the variant of the marker `M`
+/// since the system last ran.
+///
+/// Unlike `Added<M>`, inserting the same variant again does not count as entering it.
+/// Only entities with a [`VariantSince`] match.
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use bevy_ecs_enum_filter::{prelude::*, JustEntered};
+/// #[derive(Debug, EnumComponent)]
+/// enum Foo {
+///     A,
+///     B(f64),
+/// }
+///
+/// fn on_enter_b(query: Query<Entity, JustEntered<Enum!(Foo::B)>>) {
+///     for entity in &query {
+///         // ...
+///     }
+/// }
+/// # bevy_ecs::system::assert_is_system(on_enter_b);
+/// ```
+pub type JustEntered<M> = (With<M>, Changed<VariantSince<<M as EnumMarker>::Enum>>);
+
+/// A [`QueryFilter`] for entities that entered another variant of `E` since the system last ran,
+/// or got their `E` inserted for the first time.
+///
+/// Unlike `Changed<E>`, changing the fields of the current variant does not count, see [`PayloadChanged`].
+/// Only entities with a [`VariantSince`] match.
+pub type VariantChanged<E> = Changed<VariantSince<E>>;
+
+/// What [`PayloadChanged`] actually reads: the enum and when it entered its variant, on entities with the marker.
+type PayloadInner<M> = (
+    Ref<'static, <M as EnumMarker>::Enum>,
+    &'static VariantSince<<M as EnumMarker>::Enum>,
+    With<M>,
+);
+
+/// A [`QueryFilter`] for entities in the variant of the marker `M` whose fields changed since the system
+/// last ran, without entering the variant.
+///
+/// The enum counts as changed when it is inserted again in the same variant, mutated in place, or
+/// modified with [`modify_variant`](crate::modify_variant), but not when it enters the variant: the
+/// change tick of the enum is compared with the tick recorded in [`VariantSince`] by the hooks.
+/// Fields changed in the same tick as the variant was entered therefore only count as entering it,
+/// see [`VariantChanged`] and [`JustEntered`]. Only entities with a [`VariantSince`] match.
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use bevy_ecs_enum_filter::{prelude::*, PayloadChanged};
+/// #[derive(Debug, EnumComponent)]
+/// enum Foo {
+///     A,
+///     C(i32),
+/// }
+///
+/// fn on_c_changed(query: Query<&Foo, PayloadChanged<Enum!(Foo::C)>>) {
+///     for foo in &query {
+///         // ...
+///     }
+/// }
+/// # bevy_ecs::system::assert_is_system(on_c_changed);
+/// ```
+pub struct PayloadChanged<M>(PhantomData<M>);
+
+// SAFETY: every method is delegated to `PayloadInner<M>`, so the same components are accessed in the same way.
+unsafe impl<M: EnumMarker> WorldQuery for PayloadChanged<M> {
+    type Fetch<'w> = <PayloadInner<M> as WorldQuery>::Fetch<'w>;
+    type State = <PayloadInner<M> as WorldQuery>::State;
+
+    fn shrink_fetch<'wlong: 'wshort, 'wshort>(fetch: Self::Fetch<'wlong>) -> Self::Fetch<'wshort> {
+        PayloadInner::<M>::shrink_fetch(fetch)
+    }
+
+    unsafe fn init_fetch<'w>(
+        world: UnsafeWorldCell<'w>, state: &Self::State, last_run: Tick, this_run: Tick,
+    ) -> Self::Fetch<'w> {
+        // SAFETY: the caller upholds the contract of `init_fetch` for `PayloadInner<M>`, which has the same state.
+        unsafe { PayloadInner::<M>::init_fetch(world, state, last_run, this_run) }
+    }
+
+    const IS_DENSE: bool = PayloadInner::<M>::IS_DENSE;
+
+    unsafe fn set_archetype<'w>(
+        fetch: &mut Self::Fetch<'w>, state: &Self::State, archetype: &'w Archetype, table: &'w Table,
+    ) {
+        // SAFETY: as above.
+        unsafe { PayloadInner::<M>::set_archetype(fetch, state, archetype, table) }
+    }
+
+    unsafe fn set_table<'w>(fetch: &mut Self::Fetch<'w>, state: &Self::State, table: &'w Table) {
+        // SAFETY: as above.
+        unsafe { PayloadInner::<M>::set_table(fetch, state, table) }
+    }
+
+    fn update_component_access(state: &Self::State, access: &mut FilteredAccess) {
+        PayloadInner::<M>::update_component_access(state, access);
+    }
+
+    fn init_state(world: &mut World) -> Self::State {
+        PayloadInner::<M>::init_state(world)
+    }
+
+    fn get_state(components: &Components) -> Option<Self::State> {
+        PayloadInner::<M>::get_state(components)
+    }
+
+    fn matches_component_set(state: &Self::State, set_contains_id: &impl Fn(ComponentId) -> bool) -> bool {
+        PayloadInner::<M>::matches_component_set(state, set_contains_id)
+    }
+}
+
+// SAFETY: the enum and its `VariantSince` are only read, as declared by `update_component_access`.
+unsafe impl<M: EnumMarker> QueryFilter for PayloadChanged<M> {
+    const IS_ARCHETYPAL: bool = false;
+
+    unsafe fn filter_fetch(
+        state: &Self::State, fetch: &mut Self::Fetch<'_>, entity: Entity, table_row: TableRow,
+    ) -> bool {
+        // SAFETY: the caller upholds the contract of `filter_fetch`, and `set_archetype` or `set_table` set up
+        // `fetch.0` and `fetch.1`.
+        let (value, since) = unsafe {
+            (
+                <Ref<M::Enum> as QueryData>::fetch(&state.0, &mut fetch.0, entity, table_row),
+                <&VariantSince<M::Enum> as QueryData>::fetch(&state.1, &mut fetch.1, entity, table_row),
+            )
+        };
+        value
+            .zip(since)
+            .is_some_and(|(value, since)| value.is_changed() && value.last_changed() != since.tick)
+    }
+}
+
+/// What [`InVariantFor`] reads besides [`Time`]: when the entity entered its variant, on entities with the marker.
+#[cfg(feature = "bevy")]
+type InVariantForInner<M> = (&'static VariantSince<<M as EnumMarker>::Enum>, With<M>);
+
+/// A [`QueryFilter`] for entities that have been in the variant of the marker `M` for at least `MILLIS`
+/// milliseconds of [`Time`], as [`VariantSince::in_variant_for`] tells.
+///
+/// Only entities with a [`VariantSince`] match, and none while the world has no [`Time`] resource.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy_ecs_enum_filter::{prelude::*, InVariantFor};
+/// #[derive(Debug, EnumComponent)]
+/// enum Foo {
+///     Idle,
+///     Stunned,
+/// }
+///
+/// fn recover(
+///     mut commands: Commands, query: Query<Entity, InVariantFor<Enum!(Foo::Stunned), 2000>>,
+/// ) {
+///     for entity in &query {
+///         commands.entity(entity).insert(Foo::Idle);
+///     }
+/// }
+/// # bevy::ecs::system::assert_is_system(recover);
+/// ```
+#[cfg(feature = "bevy")]
+pub struct InVariantFor<M, const MILLIS: u64>(PhantomData<M>);
+
+// SAFETY: the components are delegated to `InVariantForInner<M>`, and the `Time` resource read in `init_fetch`
+// is declared by `update_component_access`.
+#[cfg(feature = "bevy")]
+unsafe impl<M: EnumMarker, const MILLIS: u64> WorldQuery for InVariantFor<M, MILLIS> {
+    type Fetch<'w> = (<InVariantForInner<M> as WorldQuery>::Fetch<'w>, Option<Duration>);
+    type State = (<InVariantForInner<M> as WorldQuery>::State, ComponentId);
+
+    fn shrink_fetch<'wlong: 'wshort, 'wshort>(fetch: Self::Fetch<'wlong>) -> Self::Fetch<'wshort> {
+        (InVariantForInner::<M>::shrink_fetch(fetch.0), fetch.1)
+    }
+
+    unsafe fn init_fetch<'w>(
+        world: UnsafeWorldCell<'w>, state: &Self::State, last_run: Tick, this_run: Tick,
+    ) -> Self::Fetch<'w> {
+        // SAFETY: the caller upholds the contract of `init_fetch` for `InVariantForInner<M>`, and the read of
+        // `Time` is part of the access of the query.
+        unsafe {
+            let now = world.get_resource::<Time>().map(Time::elapsed);
+            (InVariantForInner::<M>::init_fetch(world, &state.0, last_run, this_run), now)
+        }
+    }
+
+    const IS_DENSE: bool = InVariantForInner::<M>::IS_DENSE;
+
+    unsafe fn set_archetype<'w>(
+        fetch: &mut Self::Fetch<'w>, state: &Self::State, archetype: &'w Archetype, table: &'w Table,
+    ) {
+        // SAFETY: as above.
+        unsafe { InVariantForInner::<M>::set_archetype(&mut fetch.0, &state.0, archetype, table) }
+    }
+
+    unsafe fn set_table<'w>(fetch: &mut Self::Fetch<'w>, state: &Self::State, table: &'w Table) {
+        // SAFETY: as above.
+        unsafe { InVariantForInner::<M>::set_table(&mut fetch.0, &state.0, table) }
+    }
+
+    fn update_component_access(state: &Self::State, access: &mut FilteredAccess) {
+        InVariantForInner::<M>::update_component_access(&state.0, access);
+        access.add_resource_read(state.1);
+    }
+
+    fn init_state(world: &mut World) -> Self::State {
+        (InVariantForInner::<M>::init_state(world), world.register_resource::<Time>())
+    }
+
+    fn get_state(components: &Components) -> Option<Self::State> {
+        Some((InVariantForInner::<M>::get_state(components)?, components.resource_id::<Time>()?))
+    }
+
+    fn matches_component_set(state: &Self::State, set_contains_id: &impl Fn(ComponentId) -> bool) -> bool {
+        InVariantForInner::<M>::matches_component_set(&state.0, set_contains_id)
+    }
+}
+
+// SAFETY: `VariantSince` and `Time` are only read, as declared by `update_component_access`.
+#[cfg(feature = "bevy")]
+unsafe impl<M: EnumMarker, const MILLIS: u64> QueryFilter for InVariantFor<M, MILLIS> {
+    const IS_ARCHETYPAL: bool = false;
+
+    unsafe fn filter_fetch(
+        state: &Self::State, fetch: &mut Self::Fetch<'_>, entity: Entity, table_row: TableRow,
+    ) -> bool {
+        // SAFETY: the caller upholds the contract of `filter_fetch`, and `set_archetype` or `set_table` set up
+        // `fetch.0.0`.
+        let since =
+            unsafe { <&VariantSince<M::Enum> as QueryData>::fetch(&state.0.0, &mut fetch.0.0, entity, table_row) };
+        let duration = Duration::from_millis(MILLIS);
+        since.zip(fetch.1).is_some_and(|(since, now)| {
+            since.elapsed.is_some_and(|elapsed| now.saturating_sub(elapsed) >= duration)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    #[cfg(feature = "bevy")]
+    use bevy::ecs::{
+        query::{Added, QueryState},
+        world::World,
+    };
+    #[cfg(not(feature = "bevy"))]
+    use bevy_ecs::{
+        query::{Added, QueryState},
+        world::World,
+    };
+
+    #[allow(unused)]
+    #[derive(Clone, Debug, EnumComponent)]
+    enum Foo {
+        A,
+        B(i32),
+    }
+
+    #[test]
+    fn test_just_entered() {
+        let mut world = World::new();
+        let mut entered_b = QueryState::<Entity, JustEntered<Enum!(Foo::B)>>::new(&mut world);
+        let mut added_b = QueryState::<Entity, Added<Enum!(Foo::B)>>::new(&mut world);
+
+        // opt-in: an entity without a `VariantSince` never matches
+        let without = world.spawn(Foo::A).id();
+        assert!(!world.entity(without).contains::<VariantSince<Foo>>());
+        let entity = world.spawn((Foo::A, VariantSince::<Foo>::default())).id();
+        let spawned = world.get::<VariantSince<Foo>>(entity).unwrap().tick();
+        assert_eq!(entered_b.iter(&world).count(), 0);
+
+        world.clear_trackers();
+        world.entity_mut(entity).insert(Foo::B(1));
+        world.entity_mut(without).insert(Foo::B(1));
+        let entered = world.get::<VariantSince<Foo>>(entity).unwrap().tick();
+        assert!(entered.is_newer_than(spawned, world.read_change_tick()));
+        assert_eq!(entered_b.iter(&world).count(), 1);
+        assert_eq!(added_b.iter(&world).count(), 2);
+
+        world.clear_trackers();
+        world.entity_mut(entity).insert(Foo::B(2));
+        assert_eq!(world.get::<VariantSince<Foo>>(entity).unwrap().tick(), entered);
+        assert_eq!(entered_b.iter(&world).count(), 0);
+        assert_eq!(added_b.iter(&world).count(), 1);
+
+        // inserted later, it counts from its insertion; a clone enters its variant when it is cloned
+        world.clear_trackers();
+        world.entity_mut(without).insert(VariantSince::<Foo>::default());
+        let inserted = world.get::<VariantSince<Foo>>(without).unwrap().tick();
+        assert!(inserted.is_newer_than(entered, world.read_change_tick()));
+        world.clear_trackers();
+        let clone = world.entity_mut(entity).clone_and_spawn();
+        assert!(
+            world
+                .get::<VariantSince<Foo>>(clone)
+                .unwrap()
+                .tick()
+                .is_newer_than(inserted, world.read_change_tick())
+        );
+        assert_eq!(entered_b.iter(&world).collect::<Vec<_>>(), [clone]);
+    }
+
+    #[test]
+    fn test_payload_changed() {
+        let mut world = World::new();
+        let mut variant_changed = QueryState::<Entity, VariantChanged<Foo>>::new(&mut world);
+        let mut payload_changed = QueryState::<Entity, PayloadChanged<Enum!(Foo::B)>>::new(&mut world);
+        let mut changed = |world: &mut World| {
+            let counts = (variant_changed.iter(world).count(), payload_changed.iter(world).count());
+            world.clear_trackers();
+            counts
+        };
+
+        let entity = world.spawn((Foo::B(1), VariantSince::<Foo>::default())).id();
+        assert_eq!(changed(&mut world), (1, 0));
+
+        world.entity_mut(entity).insert(Foo::B(2));
+        assert_eq!(changed(&mut world), (0, 1));
+
+        *world.get_mut::<Foo>(entity).unwrap() = Foo::B(3);
+        assert_eq!(changed(&mut world), (0, 1));
+
+        world.entity_mut(entity).insert(Foo::A);
+        assert_eq!(changed(&mut world), (1, 0));
+
+        world.entity_mut(entity).insert(Foo::B(4));
+        assert_eq!(changed(&mut world), (1, 0));
+
+        assert_eq!(changed(&mut world), (0, 0));
+    }
+
+    #[cfg(feature = "bevy")]
+    #[test]
+    fn test_time_in_variant() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        world.resource_mut::<Time>().advance_by(Duration::from_secs(1));
+
+        let entity = world.spawn((Foo::A, VariantSince::<Foo>::default())).id();
+        world.resource_mut::<Time>().advance_by(Duration::from_secs(2));
+
+        let since = world.get::<VariantSince<Foo>>(entity).unwrap();
+        assert_eq!(since.elapsed(), Some(Duration::from_secs(1)));
+        assert_eq!(since.time_in_variant(world.resource::<Time>()), Some(Duration::from_secs(2)));
+        assert!(since.in_variant_for(world.resource::<Time>(), Duration::from_secs(2)));
+        assert!(!since.in_variant_for(world.resource::<Time>(), Duration::from_secs(3)));
+
+        let mut two_secs = QueryState::<Entity, InVariantFor<Enum!(Foo::A), 2000>>::new(&mut world);
+        let mut three_secs = QueryState::<Entity, InVariantFor<Enum!(Foo::A), 3000>>::new(&mut world);
+        assert_eq!(two_secs.iter(&world).collect::<Vec<_>>(), [entity]);
+        assert_eq!(three_secs.iter(&world).count(), 0);
+
+        world.entity_mut(entity).insert(Foo::B(1));
+        world.entity_mut(entity).insert(Foo::A);
+        assert_eq!(two_secs.iter(&world).count(), 0);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::EnumComponent;
+use bevy::{
+    app::{App, Plugin},
+    ecs::{
+        change_detection::{DetectChanges, Ref},
+        component::Component,
+        entity::Entity,
+        query::{Changed, With},
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    state::state::{FreelyMutableState, NextState, State, StateTransition, StateTransitionSystems},
+};
+use std::marker::PhantomData;
+
+/// Bridges the enum component `E` and the state `S` for the entities marked with [`StateSource`] or
+/// [`StateTarget`]. `S` defaults to `E`, for an enum deriving both `EnumComponent` and `States`.
+///
+/// Both run in the [`StateTransition`] schedule, so `S` has to be set up with `init_state` or `insert_state`:
+/// - the `E` of the [`StateSource`] is converted into `S` and set as the [`NextState<S>`] whenever it changes,
+///   right before the transitions are applied,
+/// - the [`State<S>`] is converted into `E` and inserted on every [`StateTarget`] whenever it changes,
+///   after the `OnExit` schedules and before the `OnEnter` ones, so that `OnExit` still sees the markers of
+///   the previous variant and `OnEnter` those of the new one.
+///
+/// ```
+/// # use bevy::{prelude::*, state::app::StatesPlugin};
+/// # use bevy_ecs_enum_filter::{prelude::*, EnumStatePlugin, StateSource};
+/// #[derive(States, EnumComponent, Clone, Default, Debug, Hash, PartialEq, Eq)]
+/// enum Choice {
+///     #[default]
+///     A,
+///     B,
+/// }
+///
+/// let mut app = App::new();
+/// app.add_plugins((StatesPlugin, EnumStatePlugin::<Choice>::default()))
+///     .init_state::<Choice>();
+/// app.world_mut().spawn((Choice::B, StateSource::<Choice>::default()));
+/// app.update();
+///
+/// assert_eq!(app.world().resource::<State<Choice>>().get(), &Choice::B);
+/// ```
+pub struct EnumStatePlugin<E, S = E>(PhantomData<fn() -> (E, S)>);
+
+impl<E, S> Default for EnumStatePlugin<E, S> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E, S> Plugin for EnumStatePlugin<E, S>
+where
+    E: EnumComponent + Clone + From<S>,
+    S: FreelyMutableState + From<E>,
+{
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            StateTransition,
+            (
+                source_to_state::<E, S>.before(StateTransitionSystems::DependentTransitions),
+                state_to_targets::<E, S>.in_set(StateTransitionSystems::TransitionSchedules),
+            ),
+        );
+    }
+}
+
+/// Marks the entity whose `E` drives the state `S`, with [`EnumStatePlugin<E, S>`].
+///
+/// There should be a single one: with several, the last one changed wins. Removing `E` leaves `S` as it is.
+#[derive(Component)]
+pub struct StateSource<E: EnumComponent, S: FreelyMutableState = E>(PhantomData<fn() -> (E, S)>);
+
+impl<E: EnumComponent, S: FreelyMutableState> Default for StateSource<E, S> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+/// Marks an entity whose `E` follows the state `S`, with [`EnumStatePlugin<E, S>`].
+///
+/// The entity gets the `E` of the current state at the next [`StateTransition`] after being marked.
+#[derive(Component)]
+pub struct StateTarget<E: EnumComponent, S: FreelyMutableState = E>(PhantomData<fn() -> (E, S)>);
+
+impl<E: EnumComponent, S: FreelyMutableState> Default for StateTarget<E, S> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+/// The sources whose `E` changed since the state was last set.
+type ChangedSource<E, S> = (With<StateSource<E, S>>, Changed<E>);
+
+fn source_to_state<E, S>(sources: Query<&E, ChangedSource<E, S>>, mut next_state: ResMut<NextState<S>>)
+where
+    E: EnumComponent + Clone,
+    S: FreelyMutableState + From<E>,
+{
+    // `PendingIfNeq` keeps a target that is also the source from going through the same state again
+    if let Some(value) = sources.iter().last() {
+        next_state.set_if_neq(S::from(value.clone()));
+    }
+}
+
+fn state_to_targets<E, S>(
+    mut commands: Commands, state: Res<State<S>>, targets: Query<(Entity, Ref<StateTarget<E, S>>)>,
+) where
+    E: EnumComponent + From<S>,
+    S: FreelyMutableState,
+{
+    for (entity, target) in &targets {
+        if state.is_changed() || target.is_added() {
+            commands.entity(entity).insert(E::from(state.get().clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use bevy::{
+        ecs::{query::Has, resource::Resource, system::Single},
+        state::{
+            app::{AppExtStates, StatesPlugin},
+            state::{OnEnter, OnExit, States},
+        },
+    };
+
+    #[derive(States, EnumComponent, Clone, Default, Debug, Hash, PartialEq, Eq)]
+    enum Choice {
+        #[default]
+        A,
+        B,
+    }
+
+    #[derive(States, Clone, Default, Debug, Hash, PartialEq, Eq)]
+    enum Menu {
+        #[default]
+        Main,
+        Settings,
+    }
+
+    impl From<Menu> for Choice {
+        fn from(menu: Menu) -> Self {
+            match menu {
+                Menu::Main => Choice::A,
+                Menu::Settings => Choice::B,
+            }
+        }
+    }
+
+    impl From<Choice> for Menu {
+        fn from(choice: Choice) -> Self {
+            match choice {
+                Choice::A => Menu::Main,
+                Choice::B => Menu::Settings,
+            }
+        }
+    }
+
+    #[derive(Resource, Default)]
+    struct Log(Vec<&'static str>);
+
+    #[test]
+    fn test_source_to_state() {
+        let mut app = App::new();
+        app.add_plugins((StatesPlugin, EnumStatePlugin::<Choice>::default()))
+            .init_state::<Choice>()
+            .init_resource::<Log>()
+            .add_systems(OnEnter(Choice::B), |mut log: ResMut<Log>| log.0.push("enter B"));
+
+        let player = app.world_mut().spawn((Choice::A, StateSource::<Choice>::default())).id();
+        app.update();
+        assert_eq!(app.world().resource::<State<Choice>>().get(), &Choice::A);
+
+        app.world_mut().entity_mut(player).insert(Choice::B);
+        app.update();
+        assert_eq!(app.world().resource::<State<Choice>>().get(), &Choice::B);
+        assert_eq!(app.world().resource::<Log>().0, ["enter B"]);
+
+        app.world_mut().entity_mut(player).remove::<Choice>();
+        app.update();
+        assert_eq!(app.world().resource::<State<Choice>>().get(), &Choice::B);
+    }
+
+    #[test]
+    fn test_state_to_targets() {
+        let mut app = App::new();
+        app.add_plugins((StatesPlugin, EnumStatePlugin::<Choice, Menu>::default()))
+            .init_state::<Menu>()
+            .init_resource::<Log>()
+            .add_systems(OnExit(Menu::Main), |target: Single<Has<Enum!(Choice::A)>>, mut log: ResMut<Log>| {
+                log.0.push(if *target { "exit with A" } else { "exit without A" })
+            })
+            .add_systems(OnEnter(Menu::Settings), |target: Single<Has<Enum!(Choice::B)>>, mut log: ResMut<Log>| {
+                log.0.push(if *target { "enter with B" } else { "enter without B" })
+            });
+
+        let target = app.world_mut().spawn(StateTarget::<Choice, Menu>::default()).id();
+        app.update();
+        assert!(app.world().entity(target).contains::<Enum!(Choice::A)>());
+
+        app.world_mut().resource_mut::<NextState<Menu>>().set(Menu::Settings);
+        app.update();
+        assert!(app.world().entity(target).contains::<Enum!(Choice::B)>());
+        assert_eq!(app.world().resource::<Log>().0, ["exit with A", "enter with B"]);
+    }
+}
@@ -0,0 +1,465 @@
+use crate::{
+    __private::EnumState, EnumComponent, EnumFilterError, EnumFilterErrorKind, EnumFilterErrorOrigin, EnumMarker,
+};
+#[cfg(feature = "bevy")]
+use bevy::ecs::{
+    archetype::Archetype,
+    change_detection::Tick,
+    component::{Component, ComponentId, Components, Mutable},
+    entity::Entity,
+    query::{EcsAccessType, FilteredAccess, QueryData, ReadOnlyQueryData, ReleaseStateQueryData, With, WorldQuery},
+    storage::{Table, TableRow},
+    system::EntityCommand,
+    world::{EntityWorldMut, World, unsafe_world_cell::UnsafeWorldCell},
+};
+#[cfg(not(feature = "bevy"))]
+use bevy_ecs::{
+    archetype::Archetype,
+    change_detection::Tick,
+    component::{Component, ComponentId, Components, Mutable},
+    entity::Entity,
+    query::{EcsAccessType, FilteredAccess, QueryData, ReadOnlyQueryData, ReleaseStateQueryData, With, WorldQuery},
+    storage::{Table, TableRow},
+    system::EntityCommand,
+    world::{EntityWorldMut, World, unsafe_world_cell::UnsafeWorldCell},
+};
+use std::marker::PhantomData;
+
+/// What [`VariantRef`] actually queries: the enum, on entities with the marker.
+type Inner<M> = (&'static <M as EnumMarker>::Enum, With<M>);
+/// What [`VariantMut`] actually queries, with the same state as [`Inner`].
+type InnerMut<M> = (&'static mut <M as EnumMarker>::Enum, With<M>);
+
+/// A [`QueryData`] borrowing the fields of the variant of the marker `M`, such as
+/// `foo_filters::FooBRef { v: &f64 }` for `VariantRef<Enum!(Foo::B)>`, without matching on the enum.
+/// The struct is the [`Ref`](EnumMarker::Ref) of the marker, generated in the same module.
+///
+/// Only entities with the marker are matched. An entity whose enum has already left the variant,
+/// while its marker is not removed yet, is skipped.
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use bevy_ecs_enum_filter::{prelude::*, VariantRef};
+/// #[derive(Debug, EnumComponent)]
+/// enum Foo {
+///     A,
+///     B { v: f64 },
+/// }
+///
+/// fn read_b(query: Query<VariantRef<Enum!(Foo::B)>>) {
+///     for foo_filters::FooBRef { v } in &query {
+///         println!("{v}");
+///     }
+/// }
+/// # bevy_ecs::system::assert_is_system(read_b);
+/// ```
+pub struct VariantRef<M>(PhantomData<M>);
+
+// SAFETY: every method is delegated to `Inner<M>`, so the same components are accessed in the same way.
+unsafe impl<M: EnumMarker> WorldQuery for VariantRef<M> {
+    type Fetch<'w> = <Inner<M> as WorldQuery>::Fetch<'w>;
+    type State = <Inner<M> as WorldQuery>::State;
+
+    fn shrink_fetch<'wlong: 'wshort, 'wshort>(fetch: Self::Fetch<'wlong>) -> Self::Fetch<'wshort> {
+        Inner::<M>::shrink_fetch(fetch)
+    }
+
+    unsafe fn init_fetch<'w>(
+        world: UnsafeWorldCell<'w>, state: &Self::State, last_run: Tick, this_run: Tick,
+    ) -> Self::Fetch<'w> {
+        // SAFETY: the caller upholds the contract of `init_fetch` for `Inner<M>`, which has the same state.
+        unsafe { Inner::<M>::init_fetch(world, state, last_run, this_run) }
+    }
+
+    const IS_DENSE: bool = Inner::<M>::IS_DENSE;
+
+    unsafe fn set_archetype<'w>(
+        fetch: &mut Self::Fetch<'w>, state: &Self::State, archetype: &'w Archetype, table: &'w Table,
+    ) {
+        // SAFETY: as above.
+        unsafe { Inner::<M>::set_archetype(fetch, state, archetype, table) }
+    }
+
+    unsafe fn set_table<'w>(fetch: &mut Self::Fetch<'w>, state: &Self::State, table: &'w Table) {
+        // SAFETY: as above.
+        unsafe { Inner::<M>::set_table(fetch, state, table) }
+    }
+
+    fn update_component_access(state: &Self::State, access: &mut FilteredAccess) {
+        Inner::<M>::update_component_access(state, access);
+    }
+
+    fn init_state(world: &mut World) -> Self::State {
+        Inner::<M>::init_state(world)
+    }
+
+    fn get_state(components: &Components) -> Option<Self::State> {
+        Inner::<M>::get_state(components)
+    }
+
+    fn matches_component_set(state: &Self::State, set_contains_id: &impl Fn(ComponentId) -> bool) -> bool {
+        Inner::<M>::matches_component_set(state, set_contains_id)
+    }
+}
+
+// SAFETY: only the enum is read, as by `&M::Enum`, and `IS_ARCHETYPAL` is false since entities may be skipped.
+unsafe impl<M: EnumMarker> QueryData for VariantRef<M> {
+    const IS_READ_ONLY: bool = true;
+    const IS_ARCHETYPAL: bool = false;
+    type ReadOnly = Self;
+    type Item<'w, 's> = M::Ref<'w>;
+
+    fn shrink<'wlong: 'wshort, 'wshort, 's>(item: Self::Item<'wlong, 's>) -> Self::Item<'wshort, 's> {
+        M::shrink_ref(item)
+    }
+
+    unsafe fn fetch<'w, 's>(
+        state: &'s Self::State, fetch: &mut Self::Fetch<'w>, entity: Entity, table_row: TableRow,
+    ) -> Option<Self::Item<'w, 's>> {
+        // SAFETY: the caller upholds the contract of `fetch`, and `set_archetype` or `set_table` set up `fetch.0`.
+        let value = unsafe { <&M::Enum as QueryData>::fetch(&state.0, &mut fetch.0, entity, table_row) }?;
+        M::as_variant(value)
+    }
+
+    fn iter_access(state: &Self::State) -> impl Iterator<Item = EcsAccessType<'_>> {
+        <&M::Enum as QueryData>::iter_access(&state.0)
+    }
+}
+
+// SAFETY: the enum is only read.
+unsafe impl<M: EnumMarker> ReadOnlyQueryData for VariantRef<M> {}
+
+impl<M: EnumMarker> ReleaseStateQueryData for VariantRef<M> {
+    fn release_state<'w>(item: Self::Item<'w, '_>) -> Self::Item<'w, 'static> {
+        item
+    }
+}
+
+/// A [`QueryData`] mutably borrowing the fields of the variant of the marker `M`, such as
+/// `FooBMut { v: &mut f64 }` for `VariantMut<Enum!(Foo::B)>`, for enums with mutable components.
+///
+/// The fields can be changed but not the variant, so the markers stay valid and no hook runs.
+/// Every entity yielded is marked as changed, like `&mut` would on access. Its read-only
+/// version is [`VariantRef`]. Use [`modify_variant`] for immutable enums.
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use bevy_ecs_enum_filter::{prelude::*, VariantMut};
+/// #[derive(Debug, EnumComponent)]
+/// #[enum_component(mutable)]
+/// enum Foo {
+///     A,
+///     B { v: f64 },
+/// }
+///
+/// fn grow_b(mut query: Query<VariantMut<Enum!(Foo::B)>>) {
+///     for foo_filters::FooBMut { v } in &mut query {
+///         *v *= 2.0;
+///     }
+/// }
+/// # bevy_ecs::system::assert_is_system(grow_b);
+/// ```
+pub struct VariantMut<M>(PhantomData<M>);
+
+// SAFETY: every method is delegated to `InnerMut<M>`, so the same components are accessed in the same way.
+unsafe impl<M: EnumMarker> WorldQuery for VariantMut<M>
+where
+    M::Enum: Component<Mutability = Mutable>,
+{
+    type Fetch<'w> = <InnerMut<M> as WorldQuery>::Fetch<'w>;
+    type State = <InnerMut<M> as WorldQuery>::State;
+
+    fn shrink_fetch<'wlong: 'wshort, 'wshort>(fetch: Self::Fetch<'wlong>) -> Self::Fetch<'wshort> {
+        InnerMut::<M>::shrink_fetch(fetch)
+    }
+
+    unsafe fn init_fetch<'w>(
+        world: UnsafeWorldCell<'w>, state: &Self::State, last_run: Tick, this_run: Tick,
+    ) -> Self::Fetch<'w> {
+        // SAFETY: the caller upholds the contract of `init_fetch` for `InnerMut<M>`, which has the same state.
+        unsafe { InnerMut::<M>::init_fetch(world, state, last_run, this_run) }
+    }
+
+    const IS_DENSE: bool = InnerMut::<M>::IS_DENSE;
+
+    unsafe fn set_archetype<'w>(
+        fetch: &mut Self::Fetch<'w>, state: &Self::State, archetype: &'w Archetype, table: &'w Table,
+    ) {
+        // SAFETY: as above.
+        unsafe { InnerMut::<M>::set_archetype(fetch, state, archetype, table) }
+    }
+
+    unsafe fn set_table<'w>(fetch: &mut Self::Fetch<'w>, state: &Self::State, table: &'w Table) {
+        // SAFETY: as above.
+        unsafe { InnerMut::<M>::set_table(fetch, state, table) }
+    }
+
+    fn update_component_access(state: &Self::State, access: &mut FilteredAccess) {
+        InnerMut::<M>::update_component_access(state, access);
+    }
+
+    fn init_state(world: &mut World) -> Self::State {
+        InnerMut::<M>::init_state(world)
+    }
+
+    fn get_state(components: &Components) -> Option<Self::State> {
+        InnerMut::<M>::get_state(components)
+    }
+
+    fn matches_component_set(state: &Self::State, set_contains_id: &impl Fn(ComponentId) -> bool) -> bool {
+        InnerMut::<M>::matches_component_set(state, set_contains_id)
+    }
+}
+
+// SAFETY: only the enum is written, as by `&mut M::Enum`, and `IS_ARCHETYPAL` is false since entities may be
+// skipped. `VariantRef<M>` reads the same enum and has the same state.
+unsafe impl<M: EnumMarker> QueryData for VariantMut<M>
+where
+    M::Enum: Component<Mutability = Mutable>,
+{
+    const IS_READ_ONLY: bool = false;
+    const IS_ARCHETYPAL: bool = false;
+    type ReadOnly = VariantRef<M>;
+    type Item<'w, 's> = M::Mut<'w>;
+
+    fn shrink<'wlong: 'wshort, 'wshort, 's>(item: Self::Item<'wlong, 's>) -> Self::Item<'wshort, 's> {
+        M::shrink_mut(item)
+    }
+
+    unsafe fn fetch<'w, 's>(
+        state: &'s Self::State, fetch: &mut Self::Fetch<'w>, entity: Entity, table_row: TableRow,
+    ) -> Option<Self::Item<'w, 's>> {
+        // SAFETY: the caller upholds the contract of `fetch`, and `set_archetype` or `set_table` set up `fetch.0`.
+        let value = unsafe { <&mut M::Enum as QueryData>::fetch(&state.0, &mut fetch.0, entity, table_row) }?;
+        // only an entity that is yielded is marked as changed
+        if value.variant_index() != M::INDEX {
+            return None;
+        }
+        M::as_variant_mut(value.into_inner())
+    }
+
+    fn iter_access(state: &Self::State) -> impl Iterator<Item = EcsAccessType<'_>> {
+        <&mut M::Enum as QueryData>::iter_access(&state.0)
+    }
+}
+
+impl<M: EnumMarker> ReleaseStateQueryData for VariantMut<M>
+where
+    M::Enum: Component<Mutability = Mutable>,
+{
+    fn release_state<'w>(item: Self::Item<'w, '_>) -> Self::Item<'w, 'static> {
+        item
+    }
+}
+
+/// An [`EntityCommand`] changing the fields of the variant of the marker `M` with `f`, for immutable enums
+/// that would otherwise have to be inserted again.
+///
+/// The value is replaced through [`EntityWorldMut::modify_component`], so observers and the hooks given
+/// to the derive run, but the markers are left alone: they keep their `Added` tick and no command is queued.
+/// The command fails with [`EnumFilterErrorKind::UnexpectedVariant`] if the entity is in another variant.
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use bevy_ecs_enum_filter::{prelude::*, modify_variant};
+/// #[derive(Debug, EnumComponent)]
+/// #[enum_component(immutable)]
+/// enum Foo {
+///     A,
+///     C(i32),
+/// }
+///
+/// let mut world = World::new();
+/// let entity = world.spawn(Foo::C(1)).id();
+/// world
+///     .commands()
+///     .entity(entity)
+///     .queue(modify_variant::<Enum!(Foo::C)>(|foo_filters::FooCMut(c)| *c += 1));
+/// world.flush();
+///
+/// assert!(matches!(world.get::<Foo>(entity), Some(Foo::C(2))));
+/// ```
+pub fn modify_variant<M: EnumMarker>(
+    f: impl for<'a> FnOnce(M::Mut<'a>) + Send + 'static,
+) -> impl EntityCommand<Result<(), EnumFilterError>> {
+    move |mut entity: EntityWorldMut| {
+        let id = entity.id();
+        let error = |kind| EnumFilterError {
+            enum_name: M::Enum::NAME,
+            entity: id,
+            origin: EnumFilterErrorOrigin::Command("modify_variant"),
+            kind,
+        };
+
+        let found = entity
+            .get::<M::Enum>()
+            .map(EnumComponent::variant_index)
+            .ok_or_else(|| error(EnumFilterErrorKind::MissingComponent))?;
+        if found != M::INDEX {
+            let expected = M::Enum::VARIANTS[M::INDEX];
+            let found = M::Enum::VARIANTS[found];
+            return Err(error(EnumFilterErrorKind::UnexpectedVariant { expected, found }));
+        }
+
+        if let Some(mut state) = entity.get_mut::<EnumState<M::Enum>>() {
+            state.modifying = true;
+        }
+        entity.modify_component(|value: &mut M::Enum| M::as_variant_mut(value).map(f));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VariantSince, prelude::*};
+    #[cfg(feature = "bevy")]
+    use bevy::ecs::query::{Added, Changed};
+    #[cfg(not(feature = "bevy"))]
+    use bevy_ecs::query::{Added, Changed};
+
+    #[allow(unused)]
+    #[derive(Debug, EnumComponent)]
+    enum Foo {
+        A,
+        B { v: f64, w: i32 },
+        C(i32, &'static str),
+    }
+
+    #[test]
+    fn test_variant_ref() {
+        let mut world = World::new();
+        let b = world.spawn(Foo::B { v: 1.0, w: 2 }).id();
+        world.spawn(Foo::C(3, "c"));
+        world.spawn(Foo::A);
+
+        let mut query = world.query::<(Entity, VariantRef<Enum!(Foo::B)>)>();
+        let (entity, foo_filters::FooBRef { v, w }) = query.single(&world).unwrap();
+        assert_eq!((entity, *v, *w), (b, 1.0, 2));
+
+        let mut query = world.query::<VariantRef<Enum!(Foo::C)>>();
+        let foo_filters::FooCRef(number, name) = query.single(&world).unwrap();
+        assert_eq!((*number, *name), (3, "c"));
+
+        assert!(matches!(
+            world.query::<VariantRef<Enum!(Foo::A)>>().single(&world),
+            Ok(foo_filters::FooARef)
+        ));
+
+        world.entity_mut(b).insert(Foo::C(4, "b"));
+        assert_eq!(world.query::<VariantRef<Enum!(Foo::B)>>().iter(&world).count(), 0);
+        assert_eq!(world.query::<VariantRef<Enum!(Foo::C)>>().iter(&world).count(), 2);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Other(i32);
+
+    // the views are declared in `relative::rel_filters`, one level deeper than the field types
+    mod relative {
+        use crate::prelude::*;
+
+        #[allow(unused)]
+        #[derive(Debug, EnumComponent)]
+        pub(super) enum Rel {
+            A(super::Other),
+            B(Option<Box<Self>>),
+            C(self::Local),
+            // the marker `rel_filters::Local` does not shadow the struct in the view
+            Local(Local),
+        }
+
+        #[derive(Debug, PartialEq)]
+        pub(super) struct Local;
+    }
+
+    #[test]
+    fn test_variant_ref_relative_paths() {
+        use relative::{Local, Rel, rel_filters::*};
+
+        let mut world = World::new();
+        world.spawn(Rel::A(Other(1)));
+        world.spawn(Rel::B(Some(Box::new(Rel::C(Local)))));
+
+        let RelARef(other) = world.query::<VariantRef<Enum!(Rel::A)>>().single(&world).unwrap();
+        assert_eq!(other, &Other(1));
+        let RelBRef(inner) = world.query::<VariantRef<Enum!(Rel::B)>>().single(&world).unwrap();
+        assert!(matches!(inner.as_deref(), Some(Rel::C(Local))));
+
+        world.spawn(Rel::Local(Local));
+        let RelLocalRef(local) = world.query::<VariantRef<Enum!(Rel::Local)>>().single(&world).unwrap();
+        assert_eq!(local, &Local);
+    }
+
+    #[allow(unused)]
+    #[derive(Debug, PartialEq, EnumComponent)]
+    #[enum_component(mutable)]
+    enum Mutable {
+        A,
+        B { v: f64 },
+    }
+
+    #[test]
+    fn test_variant_mut() {
+        let mut world = World::new();
+        let a = world.spawn(Mutable::A).id();
+        let b = world.spawn(Mutable::B { v: 1.0 }).id();
+        world.clear_trackers();
+
+        for mutable_filters::MutableBMut { v } in
+            world.query::<VariantMut<Enum!(Mutable::B)>>().iter_mut(&mut world)
+        {
+            *v += 1.0;
+        }
+
+        assert_eq!(world.get::<Mutable>(b), Some(&Mutable::B { v: 2.0 }));
+        let mut changed = world.query_filtered::<Entity, Changed<Mutable>>();
+        assert_eq!(changed.iter(&world).collect::<Vec<_>>(), [b]);
+        let mut added = world.query_filtered::<Entity, Added<Enum!(Mutable::B)>>();
+        assert_eq!(added.iter(&world).count(), 0);
+        assert!(world.entity(a).contains::<Enum!(Mutable::A)>());
+    }
+
+    #[allow(unused)]
+    #[derive(Debug, PartialEq, EnumComponent)]
+    #[enum_component(immutable)]
+    enum Immutable {
+        A,
+        C(i32),
+    }
+
+    #[test]
+    fn test_modify_variant() {
+        let mut world = World::new();
+        let entity = world.spawn((Immutable::C(1), VariantSince::<Immutable>::default())).id();
+        let since = world.get::<VariantSince<Immutable>>(entity).unwrap().tick();
+        world.clear_trackers();
+
+        world
+            .commands()
+            .entity(entity)
+            .queue(modify_variant::<Enum!(Immutable::C)>(|immutable_filters::ImmutableCMut(c)| *c += 1));
+        world.flush();
+
+        assert_eq!(world.get::<Immutable>(entity), Some(&Immutable::C(2)));
+        assert!(world.entity(entity).contains::<Enum!(Immutable::C)>());
+        let mut added = world.query_filtered::<Entity, Added<Enum!(Immutable::C)>>();
+        assert_eq!(added.iter(&world).count(), 0);
+        assert_eq!(world.get::<VariantSince<Immutable>>(entity).unwrap().tick(), since);
+
+        let result = modify_variant::<Enum!(Immutable::A)>(|_| {}).apply(world.entity_mut(entity));
+        let kind = EnumFilterErrorKind::UnexpectedVariant { expected: "A", found: "C" };
+        assert_eq!(
+            result,
+            Err(EnumFilterError {
+                enum_name: "Immutable",
+                entity,
+                origin: EnumFilterErrorOrigin::Command("modify_variant"),
+                kind
+            })
+        );
+
+        world.entity_mut(entity).insert(Immutable::A);
+        assert!(world.entity(entity).contains::<Enum!(Immutable::A)>());
+        assert!(!world.entity(entity).contains::<Enum!(Immutable::C)>());
+    }
+}
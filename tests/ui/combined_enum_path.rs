@@ -0,0 +1,10 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Clone, EnumComponent)]
+enum Foo {
+    A,
+}
+
+type Filter = Enum!(Foo::A, !Foo);
+
+fn main() {}
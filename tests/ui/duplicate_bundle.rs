@@ -0,0 +1,17 @@
+use bevy_ecs::component::Component;
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Component)]
+struct Cooldown(f32);
+
+fn cooldown(_: &Foo) -> Cooldown {
+    Cooldown(1.0)
+}
+
+#[derive(Clone, EnumComponent)]
+enum Foo {
+    #[enum_component(bundle = cooldown, bundle = cooldown)]
+    A,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Clone, EnumComponent)]
+#[enum_component(clone_behavior = Ignore, clone_behavior = Default)]
+enum Foo {
+    A,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Clone, EnumComponent)]
+#[enum_component(crate = bevy_ecs_enum_filter, crate = "bevy_ecs_enum_filter")]
+enum Foo {
+    A,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use bevy_ecs::entity::Entity;
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Clone, EnumComponent)]
+enum Foo {
+    Follow {
+        #[enum_component(entities)]
+        #[enum_component(entities)]
+        leader: Entity,
+    },
+}
+
+fn main() {}
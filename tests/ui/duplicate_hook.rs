@@ -0,0 +1,13 @@
+use bevy_ecs::{lifecycle::HookContext, world::DeferredWorld};
+use bevy_ecs_enum_filter::prelude::*;
+
+fn hook(_world: DeferredWorld, _context: HookContext) {}
+
+#[derive(Clone, EnumComponent)]
+#[enum_component(on_insert = hook)]
+#[enum_component(on_add = hook, on_insert = hook)]
+enum Foo {
+    A,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Clone, EnumComponent)]
+#[enum_component(immutable)]
+#[enum_component(mutability = bevy_ecs::component::Immutable)]
+enum Foo {
+    A,
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use bevy_ecs::component::Component;
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Component, Default)]
+struct Health(u32);
+
+#[derive(Clone, EnumComponent)]
+#[enum_component(require(Health))]
+#[enum_component(require(Health(100)))]
+enum Foo {
+    A,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Clone, EnumComponent)]
+#[enum_component(sparse_set, storage_type = bevy_ecs::component::StorageType::Table)]
+enum Foo {
+    A,
+}
+
+fn main() {}
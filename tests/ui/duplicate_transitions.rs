@@ -0,0 +1,11 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(EnumComponent)]
+#[enum_component(transitions(A -> B))]
+#[enum_component(strict_transitions(B -> A))]
+enum Foo {
+    A,
+    B,
+}
+
+fn main() {}
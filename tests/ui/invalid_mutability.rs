@@ -0,0 +1,9 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Clone, EnumComponent)]
+#[enum_component(table, mutability = bevy_ecs::component::Mutability)]
+enum Foo {
+    A,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Clone, EnumComponent)]
+#[enum_component(storage_type = bevy_ecs::component::StorageType::Sparse)]
+enum Foo {
+    A,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(EnumResource)]
+#[enum_component(on_insert = on_insert)]
+enum Weather {
+    Sunny,
+    Rain(f32),
+}
+
+fn main() {}
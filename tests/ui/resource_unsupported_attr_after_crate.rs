@@ -0,0 +1,10 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(EnumResource)]
+#[enum_component(crate = bevy_ecs_enum_filter, storage_type = bevy_ecs::component::StorageType::Table)]
+enum Weather {
+    Sunny,
+    Rain(f32),
+}
+
+fn main() {}
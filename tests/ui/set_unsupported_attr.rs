@@ -0,0 +1,10 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(EnumSetComponent)]
+#[enum_component(sparse_set)]
+enum Status {
+    Poisoned,
+    Burning,
+}
+
+fn main() {}
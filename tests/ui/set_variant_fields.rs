@@ -0,0 +1,9 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(EnumSetComponent)]
+enum Status {
+    Poisoned,
+    Burning(f32),
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Clone, EnumComponent)]
+struct Foo {
+    v: i32,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(EnumComponent)]
+enum Foo {
+    A,
+    B,
+}
+
+// a trailing comma makes a list of filters, not the marker
+const MARKER: Enum!(Foo::A,) = foo_filters::A;
+
+fn main() {}
@@ -0,0 +1,8 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Clone, Copy, EnumComponent)]
+union Foo {
+    v: i32,
+}
+
+fn main() {}
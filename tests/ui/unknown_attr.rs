@@ -0,0 +1,9 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Clone, EnumComponent)]
+#[enum_component(sparse_set, storage = table)]
+enum Foo {
+    A,
+}
+
+fn main() {}
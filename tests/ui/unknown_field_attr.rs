@@ -0,0 +1,9 @@
+use bevy_ecs::entity::Entity;
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Clone, EnumComponent)]
+enum Foo {
+    Target(#[enum_component(entity)] Entity),
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(EnumComponent)]
+#[enum_component(transitions(A -> B, _ -> C))]
+enum Foo {
+    A,
+    B,
+}
+
+fn main() {}
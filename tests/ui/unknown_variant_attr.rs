@@ -0,0 +1,9 @@
+use bevy_ecs_enum_filter::prelude::*;
+
+#[derive(Clone, EnumComponent)]
+enum Foo {
+    #[enum_component(sparse_set)]
+    A,
+}
+
+fn main() {}